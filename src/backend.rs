@@ -1,2 +1,3 @@
 pub mod api;
-pub(in crate::backend) mod persistence;
+mod markdown;
+pub mod persistence;
@@ -1 +1,11 @@
 pub mod article;
+pub mod attachment;
+pub mod auth;
+mod conditional;
+pub mod listing;
+mod negotiation;
+pub mod revision;
+mod schema;
+mod submission;
+pub mod tag;
+pub mod trash;
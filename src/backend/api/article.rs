@@ -1,126 +1,173 @@
 use actix_web::{HttpRequest, HttpResponse, Responder};
 use actix_web::{get, post, put, delete};
-use actix_web::http::header::{AUTHORIZATION, LAST_MODIFIED};
+use actix_web::http::header::{ContentType, ETag, HeaderValue, LAST_MODIFIED, VARY};
 use actix_web::http::StatusCode;
-use actix_web::web::{Bytes, Path};
-use chrono::{DateTime, FixedOffset, TimeZone};
+use actix_web::web::{Bytes, Data, Path, Query};
+use chrono::{DateTime, FixedOffset, Local, TimeZone};
 use log::info;
-use once_cell::sync::Lazy;
-use crate::backend::persistence::{ArticleId, ArticleRepository};
+use serde::Deserialize;
+use crate::backend::api::auth::{token_id, unauthorized, validate_master_password, ValidateResult};
+use crate::backend::api::conditional::{entity_tag, is_not_modified_by, is_precondition_failed, representation_entity_tag};
+use crate::backend::api::listing::{respond, ListQuery};
+use crate::backend::markdown::render_document;
+use crate::backend::api::negotiation::{negotiate, Representation};
+use crate::backend::api::submission;
+use crate::backend::api::schema::{json_error, ArticleDetail, Single};
+use crate::backend::persistence::{ArticleId, ArticleRepository, Rejection};
 use crate::extension::RespondPlainText;
 
-static GLOBAL_FILE: Lazy<ArticleRepository> = Lazy::new(|| ArticleRepository::new("index.json"));
-
-// TODO: らぎブログフロントエンド作りたいからCORSヘッダー設定してくれ - @yanorei32
+#[derive(Deserialize)]
+pub struct CreateQuery {
+    // create the article unpublished
+    #[serde(default)]
+    draft: bool,
+}
 
 #[post("/{article_id}")]
 #[allow(clippy::future_not_send)]
-pub async fn create(path: Path<String>, data: Bytes, req: HttpRequest) -> impl Responder {
+pub async fn create(path: Path<String>, query: Query<CreateQuery>, data: Bytes, req: HttpRequest, repository: Data<ArticleRepository>) -> impl Responder {
     if validate_master_password(&req) != ValidateResult::RightBearer {
         return unauthorized()
     }
 
     let path = ArticleId::new(path.into_inner());
     info!("create");
-    if GLOBAL_FILE.exists(&path).await.unwrap() {
-        return HttpResponse::build(StatusCode::CONFLICT)
-            .respond_with_auto_charset("already exist. Please choose another one, or overwrite with PUT request.")
-    }
-
-    let plain_text = String::from_utf8(data.to_vec());
-    if let Ok(text) = plain_text {
-        info!("valid utf8");
-        let res = GLOBAL_FILE.set_entry(path.clone(), text).await;
-        match res {
-            Ok(_) => {
-                HttpResponse::build(StatusCode::OK)
-                    .respond_with_auto_charset(format!("OK, saved as {path}.", path = &path))
-            }
-            Err(err) => {
-                HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR)
-                    .respond_with_auto_charset(format!("Exception: {err}"))
-            }
-        }
-    } else {
+    let Ok(text) = String::from_utf8(data.to_vec()) else {
         info!("invalid utf8");
-        HttpResponse::build(StatusCode::BAD_REQUEST)
+        return HttpResponse::build(StatusCode::BAD_REQUEST)
             .body("text must be valid UTF-8")
+    };
+
+    info!("valid utf8");
+    let submission = match submission::parse(&req, text) {
+        Ok(submission) => submission,
+        Err(message) => {
+            return HttpResponse::build(StatusCode::BAD_REQUEST)
+                .respond_with_auto_charset(message)
+        }
+    };
+
+    match repository.create_entry(path.clone(), submission.content, submission.metadata.unwrap_or_default(), token_id(&req), !query.draft) {
+        Ok(Ok(saved)) => {
+            HttpResponse::build(StatusCode::OK)
+                .insert_header(ETag(entity_tag(&saved)))
+                .respond_with_auto_charset(format!("OK, saved as {path}.", path = &path))
+        }
+        Ok(Err(rejection)) => rejected(rejection),
+        Err(err) => {
+            HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR)
+                .respond_with_auto_charset(format!("Exception: {err}"))
+        }
     }
 }
 
-fn fmt_http_date<Tz: TimeZone>(dt: &DateTime<Tz>) -> String {
-    let gmt_datetime = dt.with_timezone(&FixedOffset::east(0));
+pub(super) fn fmt_http_date<Tz: TimeZone>(dt: &DateTime<Tz>) -> String {
+    let gmt_datetime = dt.with_timezone(&FixedOffset::east_opt(0).unwrap());
     // Last-Modified: <day-name>, <day> <month> <year> <hour>:<minute>:<second> GMT
     gmt_datetime.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
 }
 
+// must be registered before `fetch`, which would take `.html` as a part of the id
+#[get("/{article_id}.html")]
+#[allow(clippy::future_not_send)]
+pub async fn fetch_html(path: Path<String>, req: HttpRequest, repository: Data<ArticleRepository>) -> impl Responder {
+    serve(&req, &repository, &ArticleId::new(path.into_inner()), Representation::Html)
+}
+
 #[get("/{article_id}")]
-pub async fn fetch(path: Path<String>) -> impl Responder {
-    let article_id = ArticleId::new(path.into_inner());
-    match GLOBAL_FILE.exists(&article_id).await {
-        Ok(exists) => {
-            if exists {
-                let content = GLOBAL_FILE.read_snapshot(&article_id).await;
-                match content {
-                    Ok(content) => {
-                        HttpResponse::build(StatusCode::OK)
-                            // TODO: これは正しくなく、updated_atを使う必要があるが現在はまだフィールドがない
-                            // compliant with RFC 7232 (HTTP/1.1 Conditional Requests) § 2.1.1
-                            .insert_header((LAST_MODIFIED, fmt_http_date(&content.created_at)))
-                            // TODO: Having ETag is fun, right?
-                            .respond_with_auto_charset(content.content)
-                    }
-                    Err(err) => {
-                        HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR)
-                            .respond_with_auto_charset(format!("Exception {err}"))
-                    }
-                }
-            } else {
-                HttpResponse::build(StatusCode::NOT_FOUND)
-                    .respond_with_auto_charset("Not found")
-            }
+#[allow(clippy::future_not_send)]
+pub async fn fetch(path: Path<String>, req: HttpRequest, repository: Data<ArticleRepository>) -> impl Responder {
+    let mut res = negotiate(&req).map_or_else(
+        || {
+            HttpResponse::build(StatusCode::NOT_ACCEPTABLE)
+                .respond_with_auto_charset("Available types are text/plain, text/html and application/json.")
+        },
+        |representation| serve(&req, &repository, &ArticleId::new(path.into_inner()), representation),
+    );
+    res.headers_mut().insert(VARY, HeaderValue::from_static("Accept"));
+    res
+}
+
+fn serve(req: &HttpRequest, repository: &ArticleRepository, article_id: &ArticleId, representation: Representation) -> HttpResponse {
+    let content = match repository.read_snapshot(article_id) {
+        Ok(Some(content)) if content.visible || validate_master_password(req) == ValidateResult::RightBearer => content,
+        // drafts are hidden from anonymous readers as if they did not exist
+        Ok(_) if representation == Representation::Json => return json_error(StatusCode::NOT_FOUND, "Not found"),
+        Ok(_) => {
+            return HttpResponse::build(StatusCode::NOT_FOUND)
+                .respond_with_auto_charset("Not found")
+        }
+        Err(err) if representation == Representation::Json => {
+            return json_error(StatusCode::INTERNAL_SERVER_ERROR, format!("Exception {err}"))
         }
         Err(err) => {
-            HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR)
+            return HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR)
                 .respond_with_auto_charset(format!("Exception {err}"))
         }
+    };
+
+    let (etag, last_modified) = if representation == Representation::PlainText {
+        (entity_tag(&content), content.updated_at)
+    } else {
+        (representation_entity_tag(&content), content.last_modified())
+    };
+    let status = if is_not_modified_by(req, &etag, &last_modified) {
+        StatusCode::NOT_MODIFIED
+    } else {
+        StatusCode::OK
+    };
+
+    let mut res = HttpResponse::build(status);
+    // compliant with RFC 7232 (HTTP/1.1 Conditional Requests) § 2.1.1
+    res.insert_header((LAST_MODIFIED, fmt_http_date(&last_modified)));
+    res.insert_header(ETag(etag));
+
+    if status == StatusCode::NOT_MODIFIED {
+        return res.finish()
+    }
+
+    match representation {
+        Representation::PlainText => res.respond_with_auto_charset(content.content),
+        Representation::Html => {
+            res.content_type(ContentType::html())
+                .body(render_document(&content.metadata.title.clone().unwrap_or_else(|| content.id.to_string()), &content.content))
+        }
+        Representation::Json => res.json(Single::new(ArticleDetail::from(content))),
     }
 }
 
 #[put("/{article_id}")]
 #[allow(clippy::future_not_send)]
-pub async fn update(path: Path<String>, data: Bytes, req: HttpRequest) -> impl Responder {
+pub async fn update(path: Path<String>, data: Bytes, req: HttpRequest, repository: Data<ArticleRepository>) -> impl Responder {
     if validate_master_password(&req) != ValidateResult::RightBearer {
         return unauthorized()
     }
 
     let article_id = ArticleId::new(path.into_inner());
-    match GLOBAL_FILE.exists(&article_id).await {
-        Ok(exists) => {
-            if exists {
-                let data = match String::from_utf8(data.to_vec()) {
-                    Ok(s) => s,
-                    Err(e) => {
-                        return HttpResponse::build(StatusCode::BAD_REQUEST)
-                            .respond_with_auto_charset(format!("You must provide valid UTF-8 sequence: {e}"))
-                    }
-                };
-
-                match GLOBAL_FILE.set_entry(article_id, data).await {
-                    Ok(_) => {
-                        HttpResponse::build(StatusCode::NO_CONTENT)
-                            .respond_with_auto_charset("saved")
-                    }
-                    Err(err) => {
-                        HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR)
-                            .respond_with_auto_charset(format!("Exception {err}"))
-                    }
-                }
-            } else {
-                HttpResponse::build(StatusCode::NOT_FOUND)
-                    .respond_with_auto_charset("Not found")
-            }
+    let data = match String::from_utf8(data.to_vec()) {
+        Ok(s) => s,
+        Err(e) => {
+            return HttpResponse::build(StatusCode::BAD_REQUEST)
+                .respond_with_auto_charset(format!("You must provide valid UTF-8 sequence: {e}"))
+        }
+    };
+
+    let submission = match submission::parse(&req, data) {
+        Ok(submission) => submission,
+        Err(message) => {
+            return HttpResponse::build(StatusCode::BAD_REQUEST)
+                .respond_with_auto_charset(message)
+        }
+    };
+
+    // a plain body replaces only the content
+    match repository.update_entry(&article_id, submission.content, submission.metadata, token_id(&req), |current| !is_precondition_failed(&req, current)) {
+        Ok(Ok(saved)) => {
+            HttpResponse::build(StatusCode::NO_CONTENT)
+                .insert_header(ETag(entity_tag(&saved)))
+                .respond_with_auto_charset("saved")
         }
+        Ok(Err(rejection)) => rejected(rejection),
         Err(err) => {
             HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR)
                 .respond_with_auto_charset(format!("Exception {err}"))
@@ -130,30 +177,18 @@ pub async fn update(path: Path<String>, data: Bytes, req: HttpRequest) -> impl R
 
 #[delete("/{article_id}")]
 #[allow(clippy::future_not_send)]
-pub async fn remove(path: Path<String>, req: HttpRequest) -> impl Responder {
+pub async fn remove(path: Path<String>, req: HttpRequest, repository: Data<ArticleRepository>) -> impl Responder {
     let article_id = ArticleId::new(path.into_inner());
     if validate_master_password(&req) != ValidateResult::RightBearer {
         return unauthorized()
     }
 
-    match GLOBAL_FILE.exists(&article_id).await {
-        Ok(exists) => {
-            if exists {
-                match GLOBAL_FILE.remove(&article_id).await {
-                    Ok(_) => {
-                        HttpResponse::build(StatusCode::NO_CONTENT)
-                            .respond_with_auto_charset("deleted")
-                    }
-                    Err(err) => {
-                        HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR)
-                            .respond_with_auto_charset(format!("Exception {err}"))
-                    }
-                }
-            } else {
-                HttpResponse::build(StatusCode::NOT_FOUND)
-                    .respond_with_auto_charset("Not found")
-            }
+    match repository.remove(&article_id, |current| !is_precondition_failed(&req, current)) {
+        Ok(Ok(())) => {
+            HttpResponse::build(StatusCode::NO_CONTENT)
+                .respond_with_auto_charset("deleted")
         }
+        Ok(Err(rejection)) => rejected(rejection),
         Err(err) => {
             HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR)
                 .respond_with_auto_charset(format!("Exception {err}"))
@@ -161,14 +196,34 @@ pub async fn remove(path: Path<String>, req: HttpRequest) -> impl Responder {
     }
 }
 
-#[get("/articles")]
-#[allow(clippy::pedantic)]
-pub async fn list() -> impl Responder {
-    match GLOBAL_FILE.parse_file_as_json() {
-        Ok(entries) => {
+#[derive(Deserialize)]
+pub struct PublishQuery {
+    // publish at this moment instead of now
+    at: Option<DateTime<Local>>,
+}
+
+#[post("/{article_id}/publish")]
+#[allow(clippy::future_not_send)]
+pub async fn publish(path: Path<String>, query: Query<PublishQuery>, req: HttpRequest, repository: Data<ArticleRepository>) -> impl Responder {
+    if validate_master_password(&req) != ValidateResult::RightBearer {
+        return unauthorized()
+    }
+
+    let article_id = ArticleId::new(path.into_inner());
+    let result = query.at.map_or_else(|| repository.publish(&article_id), |at| repository.schedule(&article_id, at));
+    match result {
+        Ok(Ok(published)) if published.visible => {
             HttpResponse::build(StatusCode::OK)
-                .json(entries)
+                .respond_with_auto_charset(format!("OK, published {article_id}."))
         }
+        Ok(Ok(scheduled)) => {
+            HttpResponse::build(StatusCode::OK)
+                .respond_with_auto_charset(format!(
+                    "OK, {article_id} will be published at {at}.",
+                    at = scheduled.publish_at.map(|at| at.to_rfc3339()).unwrap_or_default(),
+                ))
+        }
+        Ok(Err(rejection)) => rejected(rejection),
         Err(err) => {
             HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR)
                 .respond_with_auto_charset(format!("Exception {err}"))
@@ -176,36 +231,204 @@ pub async fn list() -> impl Responder {
     }
 }
 
-fn validate_master_password(req: &HttpRequest) -> ValidateResult {
-    if let Some(token) = req.headers().get(AUTHORIZATION) {
-        // TODO: this is subject to change
-        let correct_token = "1234567890";
-        let s = match String::from_utf8(token.as_bytes().to_vec()) {
-            Ok(s) => s,
-            Err(_) => return ValidateResult::WrongAuthMethod
-        };
-        if s.len() <= 7 || &s[0..=6] != "Bearer " {
-            return ValidateResult::WrongAuthMethod
-        }
+#[post("/{article_id}/unpublish")]
+#[allow(clippy::future_not_send)]
+pub async fn unpublish(path: Path<String>, req: HttpRequest, repository: Data<ArticleRepository>) -> impl Responder {
+    if validate_master_password(&req) != ValidateResult::RightBearer {
+        return unauthorized()
+    }
 
-        if &s[7..] != correct_token {
-            return ValidateResult::WrongBearer
+    let article_id = ArticleId::new(path.into_inner());
+    match repository.unpublish(&article_id) {
+        Ok(Ok(_)) => {
+            HttpResponse::build(StatusCode::OK)
+                .respond_with_auto_charset(format!("OK, unpublished {article_id}."))
+        }
+        Ok(Err(rejection)) => rejected(rejection),
+        Err(err) => {
+            HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR)
+                .respond_with_auto_charset(format!("Exception {err}"))
         }
-        ValidateResult::RightBearer
-    } else {
-        ValidateResult::None
     }
 }
 
-fn unauthorized() -> HttpResponse {
-    HttpResponse::build(StatusCode::UNAUTHORIZED)
-        .respond_with_auto_charset("You must be authorized to perform this action.")
+#[get("")]
+#[allow(clippy::pedantic, clippy::future_not_send)]
+pub async fn list(query: Query<ListQuery>, req: HttpRequest, repository: Data<ArticleRepository>) -> impl Responder {
+    let include_drafts = validate_master_password(&req) == ValidateResult::RightBearer;
+    let query = match query.to_page_query(include_drafts) {
+        Ok(query) => query,
+        Err(message) => return json_error(StatusCode::BAD_REQUEST, message),
+    };
+
+    match repository.list_page(&query) {
+        Ok(page) => respond(&query, &page),
+        Err(err) => json_error(StatusCode::INTERNAL_SERVER_ERROR, format!("Exception {err}")),
+    }
 }
 
-#[derive(Eq, PartialEq, Copy, Clone, Debug)]
-enum ValidateResult {
-    RightBearer,
-    WrongBearer,
-    WrongAuthMethod,
-    None,
+pub(super) fn rejected(rejection: Rejection) -> HttpResponse {
+    match rejection {
+        Rejection::AlreadyExists => {
+            HttpResponse::build(StatusCode::CONFLICT)
+                .respond_with_auto_charset("already exist. Please choose another one, or overwrite with PUT request.")
+        }
+        Rejection::InTrash => {
+            HttpResponse::build(StatusCode::CONFLICT)
+                .respond_with_auto_charset("already exist in the trash. Please choose another one, or restore it from the trash.")
+        }
+        Rejection::NotFound => {
+            HttpResponse::build(StatusCode::NOT_FOUND)
+                .respond_with_auto_charset("Not found")
+        }
+        Rejection::PreconditionFailed => {
+            HttpResponse::build(StatusCode::PRECONDITION_FAILED)
+                .respond_with_auto_charset("The article has been modified since you last saw it.")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test, App};
+    use actix_web::http::header::{ACCEPT, AUTHORIZATION, ETAG, IF_NONE_MATCH};
+    use actix_web::http::StatusCode;
+    use actix_web::web::{scope, Data};
+    use crate::backend::api::auth::BearerToken;
+    use crate::backend::api::listing::query_config;
+    use crate::backend::persistence::ArticleId;
+    use crate::backend::persistence::test_support::temp_repository;
+
+    #[actix_web::test]
+    async fn create_then_fetch() {
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(BearerToken::new("secret")))
+                .app_data(Data::new(temp_repository("create_then_fetch")))
+                .service(scope("/api/article").service((super::create, super::fetch)))
+        ).await;
+
+        let req = test::TestRequest::post().uri("/api/article/hello")
+            .insert_header((AUTHORIZATION, "Bearer secret"))
+            .set_payload("Hello, world!")
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        let req = test::TestRequest::get().uri("/api/article/hello").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(test::read_body(res).await, "Hello, world!");
+
+        // POST never overwrites
+        let req = test::TestRequest::post().uri("/api/article/hello")
+            .insert_header((AUTHORIZATION, "Bearer secret"))
+            .set_payload("Goodbye")
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CONFLICT);
+
+        let req = test::TestRequest::get().uri("/api/article/missing").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn create_requires_token() {
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(BearerToken::new("secret")))
+                .app_data(Data::new(temp_repository("create_requires_token")))
+                .service(scope("/api/article").service(super::create))
+        ).await;
+
+        let req = test::TestRequest::post().uri("/api/article/hello")
+            .insert_header((AUTHORIZATION, "Bearer wrong"))
+            .set_payload("Hello, world!")
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn front_matter_is_parsed_only_on_request() {
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(BearerToken::new("secret")))
+                .app_data(Data::new(temp_repository("front_matter_is_parsed_only_on_request")))
+                .service(scope("/api/article").service((super::create, super::fetch)))
+        ).await;
+        let body = "+++\ntitle = \"Hello\"\n+++\nHello, world!";
+
+        for (uri, expected) in [("/api/article/plain", body), ("/api/article/parsed?front_matter=true", "Hello, world!")] {
+            let req = test::TestRequest::post().uri(uri)
+                .insert_header((AUTHORIZATION, "Bearer secret"))
+                .set_payload(body)
+                .to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+            let req = test::TestRequest::get().uri(uri.split('?').next().unwrap()).to_request();
+            assert_eq!(test::call_and_read_body(&app, req).await, expected);
+        }
+
+        // an unclosed front matter is only an error when one was announced
+        let req = test::TestRequest::post().uri("/api/article/unclosed")
+            .insert_header((AUTHORIZATION, "Bearer secret"))
+            .set_payload("+++\nnot closed")
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        let req = test::TestRequest::post().uri("/api/article/unclosed-parsed?front_matter=true")
+            .insert_header((AUTHORIZATION, "Bearer secret"))
+            .set_payload("+++\nnot closed")
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn unpublishing_invalidates_json_representation() {
+        let repository = Data::new(temp_repository("unpublishing_invalidates_json_representation"));
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(BearerToken::new("secret")))
+                .app_data(repository.clone())
+                .service(scope("/api/article").service((super::create, super::fetch)))
+        ).await;
+
+        let req = test::TestRequest::post().uri("/api/article/hello")
+            .insert_header((AUTHORIZATION, "Bearer secret"))
+            .set_payload("Hello, world!")
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        let get = || test::TestRequest::get().uri("/api/article/hello")
+            .insert_header((AUTHORIZATION, "Bearer secret"))
+            .insert_header((ACCEPT, "application/json"));
+        let res = test::call_service(&app, get().to_request()).await;
+        let etag = res.headers().get(ETAG).unwrap().clone();
+        let req = get().insert_header((IF_NONE_MATCH, etag.clone())).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_MODIFIED);
+
+        // the content stays the same, but the JSON shows the visibility
+        repository.unpublish(&ArticleId::new("hello".to_string())).unwrap().unwrap();
+        let req = get().insert_header((IF_NONE_MATCH, etag)).to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: serde_json::Value = test::read_body_json(res).await;
+        assert_eq!(body["data"]["visible"], false);
+    }
+
+    #[actix_web::test]
+    async fn list_reports_malformed_query_in_json() {
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(temp_repository("list_reports_malformed_query_in_json")))
+                .service(scope("/api/articles").app_data(query_config()).service(super::list))
+        ).await;
+
+        let req = test::TestRequest::get().uri("/api/articles?limit=abc").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = test::read_body_json(res).await;
+        assert_eq!(body["error"]["status"], 400);
+
+        let req = test::TestRequest::get().uri("/api/articles?limit=0").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["error"]["status"], 400);
+    }
 }
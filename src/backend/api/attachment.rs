@@ -0,0 +1,161 @@
+use actix_web::{HttpMessage, HttpRequest, HttpResponse, Responder};
+use actix_web::{delete, get, post};
+use actix_web::http::header::{CacheControl, CacheDirective, ContentType, ETag, CONTENT_SECURITY_POLICY, CONTENT_TYPE, LAST_MODIFIED, X_CONTENT_TYPE_OPTIONS};
+use actix_web::http::StatusCode;
+use actix_web::web::{Data, Path, Payload};
+use crate::backend::api::article::{fmt_http_date, rejected};
+use crate::backend::api::auth::{unauthorized, validate_master_password, ValidateResult};
+use crate::backend::api::conditional::{attachment_entity_tag, is_attachment_not_modified};
+use crate::backend::api::schema::{json_error, AttachmentSummary, Collection};
+use crate::backend::persistence::{ArticleId, ArticleRepository, Rejection};
+use crate::config::AttachmentConfig;
+use crate::extension::RespondPlainText;
+
+// how long caches may reuse an attachment of a published article without revalidating it
+const MAX_AGE_SECONDS: u32 = 60 * 60;
+
+#[get("/{article_id}/attachments")]
+#[allow(clippy::future_not_send)]
+pub async fn list(path: Path<String>, req: HttpRequest, repository: Data<ArticleRepository>) -> impl Responder {
+    let article_id = ArticleId::new(path.into_inner());
+    match repository.read_snapshot(&article_id) {
+        Ok(Some(article)) if article.visible || validate_master_password(&req) == ValidateResult::RightBearer => {
+            HttpResponse::build(StatusCode::OK)
+                .json(Collection::new(article.attachments.iter().map(AttachmentSummary::from).collect()))
+        }
+        Ok(_) => json_error(StatusCode::NOT_FOUND, "Not found"),
+        Err(err) => json_error(StatusCode::INTERNAL_SERVER_ERROR, format!("Exception {err}")),
+    }
+}
+
+#[post("/{article_id}/attachments/{name}")]
+#[allow(clippy::future_not_send)]
+pub async fn upload(
+    path: Path<(String, String)>,
+    payload: Payload,
+    req: HttpRequest,
+    repository: Data<ArticleRepository>,
+    config: Data<AttachmentConfig>,
+) -> impl Responder {
+    if validate_master_password(&req) != ValidateResult::RightBearer {
+        return unauthorized()
+    }
+
+    let (article_id, name) = path.into_inner();
+    let article_id = ArticleId::new(article_id);
+    if !is_valid_name(&name) {
+        return HttpResponse::build(StatusCode::BAD_REQUEST)
+            .respond_with_auto_charset("The name must not be empty, start with a dot or contain a slash.")
+    }
+
+    let content = match payload.to_bytes_limited(config.size_limit).await {
+        Ok(Ok(content)) => content,
+        Ok(Err(err)) => {
+            return HttpResponse::build(StatusCode::BAD_REQUEST)
+                .respond_with_auto_charset(format!("Failed to read the body: {err}"))
+        }
+        Err(_) => {
+            return HttpResponse::build(StatusCode::PAYLOAD_TOO_LARGE)
+                .respond_with_auto_charset(format!("Attachments must not be larger than {limit} bytes.", limit = config.size_limit))
+        }
+    };
+
+    let content_type = req.get_header::<ContentType>()
+        .map_or_else(|| mime::APPLICATION_OCTET_STREAM.to_string(), |ContentType(mime)| mime.to_string());
+    match repository.add_attachment(&article_id, name.clone(), content_type, &content) {
+        Ok(Ok(saved)) => {
+            HttpResponse::build(StatusCode::OK)
+                .insert_header(ETag(attachment_entity_tag(&saved)))
+                .respond_with_auto_charset(format!("OK, saved as {name} of {article_id}."))
+        }
+        Ok(Err(Rejection::AlreadyExists)) => {
+            HttpResponse::build(StatusCode::CONFLICT)
+                .respond_with_auto_charset("already exist. Please choose another name, or delete it first.")
+        }
+        Ok(Err(rejection)) => rejected(rejection),
+        Err(err) => {
+            HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR)
+                .respond_with_auto_charset(format!("Exception {err}"))
+        }
+    }
+}
+
+#[get("/{article_id}/attachments/{name}")]
+#[allow(clippy::future_not_send)]
+pub async fn download(path: Path<(String, String)>, req: HttpRequest, repository: Data<ArticleRepository>) -> impl Responder {
+    let (article_id, name) = path.into_inner();
+    let article = match repository.read_snapshot(&ArticleId::new(article_id)) {
+        Ok(Some(article)) if article.visible || validate_master_password(&req) == ValidateResult::RightBearer => article,
+        // attachments of drafts are hidden along with them
+        Ok(_) => {
+            return HttpResponse::build(StatusCode::NOT_FOUND)
+                .respond_with_auto_charset("Not found")
+        }
+        Err(err) => {
+            return HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR)
+                .respond_with_auto_charset(format!("Exception {err}"))
+        }
+    };
+    let Some(attachment) = article.attachments.get(&name) else {
+        return HttpResponse::build(StatusCode::NOT_FOUND)
+            .respond_with_auto_charset("Not found")
+    };
+
+    let mut res = HttpResponse::build(StatusCode::OK);
+    res.insert_header((LAST_MODIFIED, fmt_http_date(&attachment.uploaded_at)));
+    res.insert_header(ETag(attachment_entity_tag(attachment)));
+    // an uploaded HTML or SVG file must not be able to run scripts on this origin, just like the rendered articles
+    res.insert_header((CONTENT_SECURITY_POLICY, "sandbox"));
+    res.insert_header((X_CONTENT_TYPE_OPTIONS, "nosniff"));
+    if article.visible {
+        res.insert_header(CacheControl(vec![CacheDirective::Public, CacheDirective::MaxAge(MAX_AGE_SECONDS)]));
+    } else {
+        // only the author can see drafts, so shared caches must not keep them
+        res.insert_header(CacheControl(vec![CacheDirective::Private, CacheDirective::NoCache]));
+    }
+
+    if is_attachment_not_modified(&req, attachment) {
+        return res.status(StatusCode::NOT_MODIFIED).finish()
+    }
+
+    match repository.read_attachment(attachment) {
+        Ok(Some(content)) => {
+            res.insert_header((CONTENT_TYPE, attachment.content_type.as_str()))
+                .body(content)
+        }
+        Ok(None) => {
+            HttpResponse::build(StatusCode::NOT_FOUND)
+                .respond_with_auto_charset("Not found")
+        }
+        Err(err) => {
+            HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR)
+                .respond_with_auto_charset(format!("Exception {err}"))
+        }
+    }
+}
+
+#[delete("/{article_id}/attachments/{name}")]
+#[allow(clippy::future_not_send)]
+pub async fn remove(path: Path<(String, String)>, req: HttpRequest, repository: Data<ArticleRepository>) -> impl Responder {
+    if validate_master_password(&req) != ValidateResult::RightBearer {
+        return unauthorized()
+    }
+
+    let (article_id, name) = path.into_inner();
+    match repository.remove_attachment(&ArticleId::new(article_id), &name) {
+        Ok(Ok(())) => {
+            HttpResponse::build(StatusCode::NO_CONTENT)
+                .respond_with_auto_charset("deleted")
+        }
+        Ok(Err(rejection)) => rejected(rejection),
+        Err(err) => {
+            HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR)
+                .respond_with_auto_charset(format!("Exception {err}"))
+        }
+    }
+}
+
+// names appear in URLs and in the front matter of the directory storage
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('.') && !name.contains(['/', '\\'])
+}
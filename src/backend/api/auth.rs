@@ -0,0 +1,111 @@
+use actix_web::{HttpRequest, HttpResponse};
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::http::StatusCode;
+use actix_web::web::Data;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use crate::backend::api::schema::json_error;
+use crate::extension::RespondPlainText;
+
+pub struct BearerToken {
+    // only the digest is kept, so that comparison takes the same time regardless of the token length
+    digest: [u8; 32],
+}
+
+impl BearerToken {
+    pub fn new(token: &str) -> Self {
+        Self {
+            digest: Sha256::digest(token.as_bytes()).into(),
+        }
+    }
+
+    // A short fingerprint that tells which token wrote a revision without revealing the token.
+    pub fn id(&self) -> String {
+        let mut id = format!("{:x}", Sha256::digest(self.digest));
+        id.truncate(12);
+        id
+    }
+
+    fn matches(&self, candidate: &str) -> bool {
+        let candidate: [u8; 32] = Sha256::digest(candidate.as_bytes()).into();
+        candidate.ct_eq(&self.digest).into()
+    }
+}
+
+pub fn validate_master_password(req: &HttpRequest) -> ValidateResult {
+    if let Some(token) = req.headers().get(AUTHORIZATION) {
+        let Ok(s) = String::from_utf8(token.as_bytes().to_vec()) else {
+            return ValidateResult::WrongAuthMethod
+        };
+        let Some(candidate) = s.strip_prefix("Bearer ").filter(|candidate| !candidate.is_empty()) else {
+            return ValidateResult::WrongAuthMethod
+        };
+
+        let Some(correct_token) = req.app_data::<Data<BearerToken>>() else {
+            return ValidateResult::WrongBearer
+        };
+
+        if !correct_token.matches(candidate) {
+            return ValidateResult::WrongBearer
+        }
+        ValidateResult::RightBearer
+    } else {
+        ValidateResult::None
+    }
+}
+
+// the id of the token the request was authorized with
+pub fn token_id(req: &HttpRequest) -> String {
+    req.app_data::<Data<BearerToken>>().map(|token| token.id()).unwrap_or_default()
+}
+
+const UNAUTHORIZED_MESSAGE: &str = "You must be authorized to perform this action.";
+
+pub fn unauthorized() -> HttpResponse {
+    HttpResponse::build(StatusCode::UNAUTHORIZED)
+        .respond_with_auto_charset(UNAUTHORIZED_MESSAGE)
+}
+
+// for the endpoints that respond with JSON
+pub fn json_unauthorized() -> HttpResponse {
+    json_error(StatusCode::UNAUTHORIZED, UNAUTHORIZED_MESSAGE)
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum ValidateResult {
+    RightBearer,
+    WrongBearer,
+    WrongAuthMethod,
+    None,
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header::{HeaderValue, AUTHORIZATION};
+    use actix_web::test::TestRequest;
+    use actix_web::web::Data;
+    use super::{validate_master_password, BearerToken, ValidateResult};
+
+    fn validate(authorization: &[u8]) -> ValidateResult {
+        let req = TestRequest::default()
+            .app_data(Data::new(BearerToken::new("secret")))
+            .insert_header((AUTHORIZATION, HeaderValue::from_bytes(authorization).unwrap()))
+            .to_http_request();
+        validate_master_password(&req)
+    }
+
+    #[test]
+    fn validates_bearer_token() {
+        assert_eq!(validate(b"Bearer secret"), ValidateResult::RightBearer);
+        assert_eq!(validate(b"Bearer wrong"), ValidateResult::WrongBearer);
+        assert_eq!(validate(b"Bearer "), ValidateResult::WrongAuthMethod);
+        assert_eq!(validate(b"Basic c2VjcmV0"), ValidateResult::WrongAuthMethod);
+    }
+
+    // a multibyte character across the end of the scheme must not be sliced through
+    #[test]
+    fn rejects_non_ascii_scheme() {
+        assert_eq!(validate("Bearxxéabc".as_bytes()), ValidateResult::WrongAuthMethod);
+        assert_eq!(validate("Bearer é".as_bytes()), ValidateResult::WrongBearer);
+    }
+}
@@ -0,0 +1,68 @@
+// RFC 7232 (HTTP/1.1 Conditional Requests)
+
+use actix_web::{HttpMessage, HttpRequest};
+use std::time::SystemTime;
+use actix_web::http::header::{EntityTag, IfMatch, IfModifiedSince, IfNoneMatch, IfUnmodifiedSince};
+use chrono::{DateTime, Local, Utc};
+use sha2::{Digest, Sha256};
+use crate::backend::api::schema::ArticleDetail;
+use crate::backend::persistence::{Article, Attachment};
+
+// validates the plain text, which is the stored content itself
+pub fn entity_tag(article: &Article) -> EntityTag {
+    let mut hasher = Sha256::new();
+    hasher.update(article.revision.to_be_bytes());
+    hasher.update(article.content.as_bytes());
+    EntityTag::new_strong(format!("{:x}", hasher.finalize()))
+}
+
+// The HTML and JSON representations also show the visibility, the metadata and the attachments, so they are
+// validated by everything the JSON one contains. Weak, as they are not byte-identical with the stored text.
+pub fn representation_entity_tag(article: &Article) -> EntityTag {
+    // cannot fail, as it has no maps with non-string keys
+    let detail = serde_json::to_vec(&ArticleDetail::from(article.clone())).unwrap();
+    EntityTag::new_weak(format!("{:x}", Sha256::digest(detail)))
+}
+
+// attachments are content-addressed, so their hash is a strong validator by itself
+pub fn attachment_entity_tag(attachment: &Attachment) -> EntityTag {
+    EntityTag::new_strong(attachment.sha256.clone())
+}
+
+// HTTP-date has a resolution of one second, so sub-second part of updated_at must be ignored
+fn is_modified_since(updated_at: &DateTime<Local>, since: SystemTime) -> bool {
+    updated_at.timestamp() > DateTime::<Utc>::from(since).timestamp()
+}
+
+pub fn is_attachment_not_modified(req: &HttpRequest, attachment: &Attachment) -> bool {
+    is_not_modified_by(req, &attachment_entity_tag(attachment), &attachment.uploaded_at)
+}
+
+// § 3.2, § 3.3: GET should be answered with 304 Not Modified
+// If-Modified-Since is ignored when If-None-Match is present (§ 6)
+pub fn is_not_modified_by(req: &HttpRequest, current: &EntityTag, updated_at: &DateTime<Local>) -> bool {
+    match req.get_header::<IfNoneMatch>() {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(current)),
+        None => {
+            req.get_header::<IfModifiedSince>()
+                .is_some_and(|IfModifiedSince(since)| !is_modified_since(updated_at, since.into()))
+        }
+    }
+}
+
+// § 3.1, § 3.4: state-changing requests should be answered with 412 Precondition Failed
+// If-Unmodified-Since is ignored when If-Match is present (§ 6)
+pub fn is_precondition_failed(req: &HttpRequest, article: &Article) -> bool {
+    match req.get_header::<IfMatch>() {
+        Some(IfMatch::Any) => false,
+        Some(IfMatch::Items(tags)) => {
+            let current = entity_tag(article);
+            !tags.iter().any(|tag| tag.strong_eq(&current))
+        }
+        None => {
+            req.get_header::<IfUnmodifiedSince>()
+                .is_some_and(|IfUnmodifiedSince(since)| is_modified_since(&article.updated_at, since.into()))
+        }
+    }
+}
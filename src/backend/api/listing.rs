@@ -0,0 +1,248 @@
+use std::fmt::Write as _;
+use actix_web::HttpResponse;
+use actix_web::error::InternalError;
+use actix_web::http::StatusCode;
+use actix_web::web::QueryConfig;
+use chrono::{DateTime, Local, SecondsFormat};
+use serde::Deserialize;
+use crate::backend::api::schema::{json_error, ArticleSummary, PageResponse, SCHEMA_VERSION};
+use crate::backend::persistence::ArticleId;
+use crate::backend::persistence::page::{ArticlePage, PageQuery, SortKey, SortOrder};
+
+// The listings respond with JSON, so malformed query strings are reported in JSON too.
+pub fn query_config() -> QueryConfig {
+    QueryConfig::default().error_handler(|err, _| {
+        let response = json_error(StatusCode::BAD_REQUEST, err.to_string());
+        InternalError::from_response(err, response).into()
+    })
+}
+
+const DEFAULT_LIMIT: usize = 20;
+const MAX_LIMIT: usize = 100;
+
+// Query string of the article listings. Either `offset` or `cursor` may be used to page through the results.
+#[derive(Deserialize)]
+pub struct ListQuery {
+    limit: Option<usize>,
+    offset: Option<usize>,
+    // `next_cursor` of the previous page
+    cursor: Option<String>,
+    #[serde(default)]
+    sort: SortKey,
+    #[serde(default)]
+    order: SortOrder,
+    // inclusive bound on the sort key
+    since: Option<DateTime<Local>>,
+    // exclusive bound on the sort key
+    until: Option<DateTime<Local>>,
+}
+
+impl ListQuery {
+    // Drafts are included only if `include_drafts`. The error is a message for the client.
+    pub fn to_page_query(&self, include_drafts: bool) -> Result<PageQuery, String> {
+        let limit = self.limit.unwrap_or(DEFAULT_LIMIT);
+        if limit == 0 || limit > MAX_LIMIT {
+            return Err(format!("limit must be between 1 and {MAX_LIMIT}"))
+        }
+
+        let after = match (&self.cursor, self.offset) {
+            (Some(_), Some(_)) => return Err("offset and cursor cannot be used together".to_string()),
+            (Some(cursor), None) => Some(decode_cursor(cursor, self.sort).ok_or("invalid cursor")?),
+            (None, _) => None,
+        };
+
+        Ok(PageQuery {
+            sort: self.sort,
+            order: self.order,
+            since: self.since,
+            until: self.until,
+            after,
+            offset: self.offset.unwrap_or(0),
+            limit,
+            visible_at: (!include_drafts).then(Local::now),
+        })
+    }
+}
+
+// bodies are left out; fetch each article to read it
+pub fn respond(query: &PageQuery, page: &ArticlePage) -> HttpResponse {
+    let next_cursor = if page.has_more {
+        page.articles.last().map(|last| encode_cursor(query.sort, query.sort.of(last), &last.id))
+    } else {
+        None
+    };
+
+    HttpResponse::build(StatusCode::OK)
+        .json(PageResponse {
+            schema_version: SCHEMA_VERSION,
+            data: page.articles.iter().map(ArticleSummary::from).collect(),
+            total: page.total,
+            next_cursor,
+        })
+}
+
+// The cursor is the sort key and the id of the last article of the page, hex-encoded so that it is opaque and URL-safe.
+fn encode_cursor(key: SortKey, timestamp: DateTime<Local>, id: &ArticleId) -> String {
+    let plain = format!(
+        "{key}\n{timestamp}\n{id}",
+        key = key.name(),
+        timestamp = timestamp.to_rfc3339_opts(SecondsFormat::Nanos, true),
+    );
+
+    plain.bytes().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+fn decode_cursor(cursor: &str, key: SortKey) -> Option<(DateTime<Local>, ArticleId)> {
+    if !cursor.len().is_multiple_of(2) {
+        return None
+    }
+    let bytes = (0..cursor.len()).step_by(2)
+        .map(|i| cursor.get(i..i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+        .collect::<Option<Vec<_>>>()?;
+    let plain = String::from_utf8(bytes).ok()?;
+
+    let mut parts = plain.splitn(3, '\n');
+    // a cursor taken with another sort key points somewhere meaningless
+    if parts.next()? != key.name() {
+        return None
+    }
+    let timestamp = DateTime::parse_from_rfc3339(parts.next()?).ok()?.with_timezone(&Local);
+    let id = ArticleId::new(parts.next()?.to_string());
+
+    Some((timestamp, id))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration, Local};
+    use crate::backend::persistence::page::{page_of, SortKey, SortOrder};
+    use crate::backend::persistence::{Article, ArticleId};
+    use crate::backend::persistence::test_support::published_article;
+    use super::{decode_cursor, encode_cursor, ListQuery};
+
+    fn list_query(limit: usize) -> ListQuery {
+        ListQuery {
+            limit: Some(limit),
+            offset: None,
+            cursor: None,
+            sort: SortKey::Created,
+            order: SortOrder::Desc,
+            since: None,
+            until: None,
+        }
+    }
+
+    fn ids(articles: &[Article]) -> Vec<String> {
+        articles.iter().map(|article| article.id.to_string()).collect()
+    }
+
+    // three articles share a timestamp, so pages must be split in the middle of a tie
+    fn articles(now: DateTime<Local>) -> Vec<Article> {
+        vec![
+            published_article("e", now - Duration::hours(2)),
+            published_article("b", now - Duration::hours(1)),
+            published_article("d", now - Duration::hours(1)),
+            published_article("a", now - Duration::hours(1)),
+            published_article("c", now),
+        ]
+    }
+
+    #[test]
+    fn cursor_pages_through_ties_without_overlaps_or_gaps() {
+        let articles = articles(Local::now());
+
+        for (order, expected) in [(SortOrder::Desc, ["c", "d", "b", "a", "e"]), (SortOrder::Asc, ["e", "a", "b", "d", "c"])] {
+            let mut seen = vec![];
+            let mut cursor = None;
+            loop {
+                let list_query = ListQuery {
+                    cursor: cursor.take(),
+                    order,
+                    ..list_query(2)
+                };
+                let query = list_query.to_page_query(false).unwrap();
+                let page = page_of(&articles, &query);
+                assert_eq!(page.total, 5);
+
+                seen.extend(ids(&page.articles));
+                if !page.has_more {
+                    break
+                }
+                let last = page.articles.last().unwrap();
+                cursor = Some(encode_cursor(SortKey::Created, last.created_at, &last.id));
+            }
+
+            assert_eq!(seen, expected);
+        }
+    }
+
+    #[test]
+    fn offset_skips_articles() {
+        let articles = articles(Local::now());
+        let query = ListQuery {
+            offset: Some(3),
+            ..list_query(10)
+        }.to_page_query(false).unwrap();
+
+        let page = page_of(&articles, &query);
+        assert_eq!(ids(&page.articles), ["a", "e"]);
+        assert!(!page.has_more);
+    }
+
+    #[test]
+    fn cursor_round_trips() {
+        let timestamp = Local::now();
+        let id = ArticleId::new("with\nnewline".to_string());
+        let cursor = encode_cursor(SortKey::Updated, timestamp, &id);
+
+        assert_eq!(decode_cursor(&cursor, SortKey::Updated), Some((timestamp, id)));
+        // taken with another sort key
+        assert_eq!(decode_cursor(&cursor, SortKey::Created), None);
+        assert_eq!(decode_cursor("zz", SortKey::Updated), None);
+        assert_eq!(decode_cursor(&cursor[1..], SortKey::Updated), None);
+    }
+
+    #[test]
+    fn rejects_invalid_queries() {
+        let offset_and_cursor = ListQuery {
+            offset: Some(1),
+            cursor: Some(encode_cursor(SortKey::Created, Local::now(), &ArticleId::new("a".to_string()))),
+            ..list_query(10)
+        };
+        assert!(offset_and_cursor.to_page_query(false).is_err());
+        assert!(list_query(0).to_page_query(false).is_err());
+        assert!(list_query(101).to_page_query(false).is_err());
+        assert!(ListQuery {
+            cursor: Some("not a cursor".to_string()),
+            ..list_query(10)
+        }.to_page_query(false).is_err());
+    }
+
+    #[test]
+    fn since_is_inclusive_and_until_is_exclusive() {
+        let now = Local::now();
+        let articles = articles(now);
+        let query = ListQuery {
+            since: Some(now - Duration::hours(1)),
+            until: Some(now),
+            ..list_query(10)
+        }.to_page_query(false).unwrap();
+
+        let page = page_of(&articles, &query);
+        assert_eq!(ids(&page.articles), ["d", "b", "a"]);
+        assert_eq!(page.total, 3);
+    }
+
+    #[test]
+    fn drafts_are_listed_only_when_asked() {
+        let now = Local::now();
+        let mut articles = articles(now);
+        articles[0].visible = false;
+
+        assert_eq!(page_of(&articles, &list_query(10).to_page_query(false).unwrap()).total, 4);
+        assert_eq!(page_of(&articles, &list_query(10).to_page_query(true).unwrap()).total, 5);
+    }
+}
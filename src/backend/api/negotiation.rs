@@ -0,0 +1,52 @@
+// RFC 9110 § 12.5.1 (Accept)
+
+use actix_web::{HttpMessage, HttpRequest};
+use actix_web::http::header::{Accept, Quality};
+use mime::Mime;
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Representation {
+    PlainText,
+    Html,
+    Json,
+}
+
+impl Representation {
+    // in the order the server prefers them when the client accepts several equally
+    const ALL: [Self; 3] = [Self::PlainText, Self::Html, Self::Json];
+
+    const fn mime(self) -> Mime {
+        match self {
+            Self::PlainText => mime::TEXT_PLAIN,
+            Self::Html => mime::TEXT_HTML,
+            Self::Json => mime::APPLICATION_JSON,
+        }
+    }
+}
+
+// None if the client accepts none of the representations, which should be answered with 406 Not Acceptable.
+// A request without `Accept` gets the plain text, as it always has.
+pub fn negotiate(req: &HttpRequest) -> Option<Representation> {
+    let Some(accept) = req.get_header::<Accept>() else {
+        return Some(Representation::PlainText)
+    };
+
+    // `text/html;q=0` rules out HTML even if `*/*` is also listed
+    let refused: Vec<_> = accept.iter()
+        .filter(|item| item.quality == Quality::ZERO)
+        .map(|item| item.item.clone())
+        .collect();
+    let acceptable = Accept(accept.0.into_iter().filter(|item| item.quality != Quality::ZERO).collect());
+
+    acceptable.ranked().iter().find_map(|range| {
+        Representation::ALL.into_iter().find(|representation| {
+            let mime = representation.mime();
+            matches(range, &mime) && !refused.iter().any(|refused| refused.essence_str() == mime.essence_str())
+        })
+    })
+}
+
+fn matches(range: &Mime, mime: &Mime) -> bool {
+    range.type_() == mime::STAR
+        || (range.type_() == mime.type_() && (range.subtype() == mime::STAR || range.subtype() == mime.subtype()))
+}
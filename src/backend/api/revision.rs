@@ -0,0 +1,121 @@
+use actix_web::{HttpRequest, HttpResponse, Responder};
+use actix_web::{get, post};
+use actix_web::http::header::ETag;
+use actix_web::http::StatusCode;
+use actix_web::web::{Data, Path};
+use anyhow::Result;
+use similar::TextDiff;
+use crate::backend::api::article::rejected;
+use crate::backend::api::auth::{json_unauthorized, token_id, unauthorized, validate_master_password, ValidateResult};
+use crate::backend::api::conditional::{entity_tag, is_precondition_failed};
+use crate::backend::api::schema::{json_error, Collection, RevisionSummary};
+use crate::backend::persistence::{ArticleId, ArticleRepository, Revision};
+use crate::extension::RespondPlainText;
+
+// The history may contain drafts of content that was never meant to stay public, so every endpoint needs the token.
+
+#[get("/{article_id}/revisions")]
+#[allow(clippy::future_not_send)]
+pub async fn list(path: Path<String>, req: HttpRequest, repository: Data<ArticleRepository>) -> impl Responder {
+    if validate_master_password(&req) != ValidateResult::RightBearer {
+        return json_unauthorized()
+    }
+
+    let article_id = ArticleId::new(path.into_inner());
+    match repository.revisions(&article_id) {
+        Ok(Some(revisions)) => {
+            // contents are left out; fetch a revision to see it
+            HttpResponse::build(StatusCode::OK)
+                .json(Collection::new(revisions.iter().map(RevisionSummary::from).collect()))
+        }
+        Ok(None) => json_error(StatusCode::NOT_FOUND, "Not found"),
+        Err(err) => json_error(StatusCode::INTERNAL_SERVER_ERROR, format!("Exception {err}")),
+    }
+}
+
+#[get("/{article_id}/revisions/{number}")]
+#[allow(clippy::future_not_send)]
+pub async fn fetch(path: Path<(String, u64)>, req: HttpRequest, repository: Data<ArticleRepository>) -> impl Responder {
+    if validate_master_password(&req) != ValidateResult::RightBearer {
+        return unauthorized()
+    }
+
+    let (article_id, number) = path.into_inner();
+    match find_revisions(&repository, &ArticleId::new(article_id), &[number]) {
+        Ok(Some(mut found)) => {
+            HttpResponse::build(StatusCode::OK)
+                .respond_with_auto_charset(found.remove(0).content)
+        }
+        Ok(None) => {
+            HttpResponse::build(StatusCode::NOT_FOUND)
+                .respond_with_auto_charset("Not found")
+        }
+        Err(err) => {
+            HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR)
+                .respond_with_auto_charset(format!("Exception {err}"))
+        }
+    }
+}
+
+#[get("/{article_id}/revisions/{from}/diff/{to}")]
+#[allow(clippy::future_not_send)]
+pub async fn diff(path: Path<(String, u64, u64)>, req: HttpRequest, repository: Data<ArticleRepository>) -> impl Responder {
+    if validate_master_password(&req) != ValidateResult::RightBearer {
+        return unauthorized()
+    }
+
+    let (article_id, from, to) = path.into_inner();
+    let article_id = ArticleId::new(article_id);
+    match find_revisions(&repository, &article_id, &[from, to]) {
+        Ok(Some(found)) => {
+            let diff = TextDiff::from_lines(&found[0].content, &found[1].content)
+                .unified_diff()
+                .header(&format!("{article_id}@{from}"), &format!("{article_id}@{to}"))
+                .to_string();
+            HttpResponse::build(StatusCode::OK)
+                .respond_with_auto_charset(diff)
+        }
+        Ok(None) => {
+            HttpResponse::build(StatusCode::NOT_FOUND)
+                .respond_with_auto_charset("Not found")
+        }
+        Err(err) => {
+            HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR)
+                .respond_with_auto_charset(format!("Exception {err}"))
+        }
+    }
+}
+
+#[post("/{article_id}/revisions/{number}/restore")]
+#[allow(clippy::future_not_send)]
+pub async fn restore(path: Path<(String, u64)>, req: HttpRequest, repository: Data<ArticleRepository>) -> impl Responder {
+    if validate_master_password(&req) != ValidateResult::RightBearer {
+        return unauthorized()
+    }
+
+    let (article_id, number) = path.into_inner();
+    let article_id = ArticleId::new(article_id);
+    match repository.restore_revision(&article_id, number, token_id(&req), |current| !is_precondition_failed(&req, current)) {
+        Ok(Ok(saved)) => {
+            HttpResponse::build(StatusCode::OK)
+                .insert_header(ETag(entity_tag(&saved)))
+                .respond_with_auto_charset(format!("OK, restored revision {number} as revision {revision}.", revision = saved.revision))
+        }
+        Ok(Err(rejection)) => rejected(rejection),
+        Err(err) => {
+            HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR)
+                .respond_with_auto_charset(format!("Exception {err}"))
+        }
+    }
+}
+
+// Returns the revisions in the order of `numbers`; None if the article or any of them does not exist.
+fn find_revisions(repository: &ArticleRepository, article_id: &ArticleId, numbers: &[u64]) -> Result<Option<Vec<Revision>>> {
+    let Some(revisions) = repository.revisions(article_id)? else {
+        return Ok(None)
+    };
+
+    Ok(numbers.iter().map(|number| {
+        revisions.iter().find(|revision| revision.number == *number).cloned()
+    }).collect())
+}
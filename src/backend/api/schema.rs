@@ -0,0 +1,196 @@
+// The JSON bodies the API responds with. They are kept apart from the persisted structs,
+// so that the storages can change without breaking clients.
+//
+// Every body carries `schema_version`. It is incremented only when a field is removed or changes its meaning;
+// new fields may be added within the same version, so clients must ignore fields they do not know.
+//
+// Version 1:
+// - article summary: id, title, summary, tags, author, created_at, updated_at, revision, visible,
+//   published_at, publish_at, and deleted_at for articles in the trash
+// - article detail: the summary, content and attachments
+// - attachment summary: name, content_type, size, sha256, uploaded_at
+// - revision summary: number, created_at, token_id
+// - tag summary: name, count
+// - single entries: `{ "schema_version", "data": {...} }`
+// - collections: `{ "schema_version", "data": [...] }`, plus `total` and `next_cursor` when paginated
+// - errors: `{ "schema_version", "error": { "status", "message" } }`
+
+use actix_web::HttpResponse;
+use actix_web::http::StatusCode;
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use crate::backend::persistence::{Article, ArticleId, Attachment, Revision};
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+pub struct ArticleSummary {
+    pub id: ArticleId,
+    pub title: Option<String>,
+    pub summary: Option<String>,
+    pub tags: Vec<String>,
+    pub author: Option<String>,
+    pub created_at: DateTime<Local>,
+    pub updated_at: DateTime<Local>,
+    pub revision: u64,
+    pub visible: bool,
+    pub published_at: Option<DateTime<Local>>,
+    pub publish_at: Option<DateTime<Local>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Local>>,
+}
+
+impl From<&Article> for ArticleSummary {
+    fn from(article: &Article) -> Self {
+        Self {
+            id: article.id.clone(),
+            title: article.metadata.title.clone(),
+            summary: article.metadata.summary.clone(),
+            tags: article.metadata.tags.iter().cloned().collect(),
+            author: article.metadata.author.clone(),
+            created_at: article.created_at,
+            updated_at: article.updated_at,
+            revision: article.revision,
+            visible: article.visible,
+            published_at: article.published_at,
+            publish_at: article.publish_at,
+            deleted_at: article.deleted_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ArticleDetail {
+    #[serde(flatten)]
+    pub summary: ArticleSummary,
+    pub content: String,
+    pub attachments: Vec<AttachmentSummary>,
+}
+
+impl From<Article> for ArticleDetail {
+    fn from(article: Article) -> Self {
+        Self {
+            summary: ArticleSummary::from(&article),
+            attachments: article.attachments.iter().map(AttachmentSummary::from).collect(),
+            content: article.content,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct AttachmentSummary {
+    pub name: String,
+    pub content_type: String,
+    pub size: u64,
+    pub sha256: String,
+    pub uploaded_at: DateTime<Local>,
+}
+
+impl From<(&String, &Attachment)> for AttachmentSummary {
+    fn from((name, attachment): (&String, &Attachment)) -> Self {
+        Self {
+            name: name.clone(),
+            content_type: attachment.content_type.clone(),
+            size: attachment.size,
+            sha256: attachment.sha256.clone(),
+            uploaded_at: attachment.uploaded_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct RevisionSummary {
+    pub number: u64,
+    pub created_at: DateTime<Local>,
+    pub token_id: Option<String>,
+}
+
+impl From<&Revision> for RevisionSummary {
+    fn from(revision: &Revision) -> Self {
+        Self {
+            number: revision.number,
+            created_at: revision.created_at,
+            token_id: revision.token_id.clone(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct TagSummary {
+    pub name: String,
+    // number of articles with the tag
+    pub count: usize,
+}
+
+impl From<(String, usize)> for TagSummary {
+    fn from((name, count): (String, usize)) -> Self {
+        Self {
+            name,
+            count,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Single<T> {
+    pub schema_version: u32,
+    pub data: T,
+}
+
+impl<T> Single<T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            data,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Collection<T> {
+    pub schema_version: u32,
+    pub data: Vec<T>,
+}
+
+impl<T> Collection<T> {
+    pub const fn new(data: Vec<T>) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            data,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct PageResponse<T> {
+    pub schema_version: u32,
+    pub data: Vec<T>,
+    // number of entries across all pages
+    pub total: usize,
+    // pass as `cursor` to get the next page; None on the last page
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ErrorResponse {
+    pub schema_version: u32,
+    pub error: ErrorBody,
+}
+
+#[derive(Serialize)]
+pub struct ErrorBody {
+    pub status: u16,
+    pub message: String,
+}
+
+// for the endpoints that respond with JSON
+pub fn json_error(status: StatusCode, message: impl Into<String>) -> HttpResponse {
+    HttpResponse::build(status)
+        .json(ErrorResponse {
+            schema_version: SCHEMA_VERSION,
+            error: ErrorBody {
+                status: status.as_u16(),
+                message: message.into(),
+            },
+        })
+}
@@ -0,0 +1,106 @@
+// The bodies of POST and PUT on an article. Besides the plain content, they can carry the metadata
+// either as JSON (`Content-Type: application/json`):
+//
+// { "content": "...", "title": "Hello", "summary": "...", "tags": ["diary"], "author": "..." }
+//
+// or, if the request has `?front_matter=true`, as a TOML front matter in front of the content,
+// in the same form as the directory storage:
+//
+// +++
+// title = "Hello"
+// tags = ["diary"]
+// +++
+// content...
+//
+// Any other body is stored as it is, even if it happens to start with `+++`.
+
+use actix_web::{HttpMessage, HttpRequest};
+use actix_web::http::header::ContentType;
+use actix_web::web::Query;
+use serde::Deserialize;
+use crate::backend::markdown::split_front_matter;
+use crate::backend::persistence::Metadata;
+
+pub struct Submission {
+    pub content: String,
+    // None if the body is the plain content
+    pub metadata: Option<Metadata>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonSubmission {
+    content: String,
+    title: Option<String>,
+    summary: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    author: Option<String>,
+}
+
+// the front matter has only the metadata; the rest of the article is managed by the server
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FrontMatter {
+    title: Option<String>,
+    summary: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    author: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SubmissionQuery {
+    #[serde(default)]
+    front_matter: bool,
+}
+
+// Err is a message for 400 Bad Request.
+pub fn parse(req: &HttpRequest, text: String) -> Result<Submission, String> {
+    let is_json = req.get_header::<ContentType>()
+        .is_some_and(|ContentType(mime)| mime.essence_str() == mime::APPLICATION_JSON.essence_str());
+
+    if is_json {
+        let body: JsonSubmission = serde_json::from_str(&text).map_err(|e| format!("Invalid JSON body: {e}"))?;
+        return Ok(Submission {
+            content: body.content,
+            metadata: Some(metadata(body.title, body.summary, body.tags, body.author)?),
+        })
+    }
+
+    let query = Query::<SubmissionQuery>::from_query(req.query_string()).map_err(|e| format!("Invalid query string: {e}"))?;
+    if !query.front_matter {
+        return Ok(Submission { content: text, metadata: None })
+    }
+
+    match split_front_matter(&text) {
+        Ok(Some((header, content))) => {
+            let front_matter: FrontMatter = toml::from_str(header).map_err(|e| format!("Invalid front matter: {e}"))?;
+            Ok(Submission {
+                content: content.to_string(),
+                metadata: Some(metadata(front_matter.title, front_matter.summary, front_matter.tags, front_matter.author)?),
+            })
+        }
+        Ok(None) => Ok(Submission { content: text, metadata: None }),
+        Err(e) => Err(format!("Invalid front matter: {e}")),
+    }
+}
+
+fn metadata(title: Option<String>, summary: Option<String>, tags: Vec<String>, author: Option<String>) -> Result<Metadata, String> {
+    // tags appear in URLs, so they are kept free of surrounding spaces and slashes
+    let tags = tags.into_iter().map(|tag| {
+        let tag = tag.trim();
+        if tag.is_empty() || tag.contains('/') {
+            Err(format!("{tag:?} cannot be used as a tag; tags must not be empty or contain a slash."))
+        } else {
+            Ok(tag.to_string())
+        }
+    }).collect::<Result<_, _>>()?;
+
+    Ok(Metadata {
+        title,
+        summary,
+        tags,
+        author,
+    })
+}
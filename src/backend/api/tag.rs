@@ -0,0 +1,73 @@
+use actix_web::{HttpRequest, HttpResponse, Responder};
+use actix_web::get;
+use actix_web::http::StatusCode;
+use actix_web::web::{Data, Path, Query};
+use chrono::Local;
+use crate::backend::api::auth::{validate_master_password, ValidateResult};
+use crate::backend::api::listing::{respond, ListQuery};
+use crate::backend::api::schema::{json_error, Collection, TagSummary};
+use crate::backend::persistence::ArticleRepository;
+
+// drafts are counted only for the author
+#[get("")]
+#[allow(clippy::future_not_send)]
+pub async fn list(req: HttpRequest, repository: Data<ArticleRepository>) -> impl Responder {
+    let visible_at = (validate_master_password(&req) != ValidateResult::RightBearer).then(Local::now);
+    HttpResponse::build(StatusCode::OK)
+        .json(Collection::new(repository.tags(visible_at).into_iter().map(TagSummary::from).collect()))
+}
+
+#[get("/{tag}/articles")]
+#[allow(clippy::pedantic, clippy::future_not_send)]
+pub async fn articles(path: Path<String>, query: Query<ListQuery>, req: HttpRequest, repository: Data<ArticleRepository>) -> impl Responder {
+    let include_drafts = validate_master_password(&req) == ValidateResult::RightBearer;
+    let query = match query.to_page_query(include_drafts) {
+        Ok(query) => query,
+        Err(message) => return json_error(StatusCode::BAD_REQUEST, message),
+    };
+
+    // a tag exists as long as an article has it, even if the filters leave out all of them
+    let tag = path.into_inner();
+    if !repository.has_tag(&tag, query.visible_at) {
+        return json_error(StatusCode::NOT_FOUND, "Not found")
+    }
+
+    match repository.tagged(&tag, &query) {
+        Ok(page) => respond(&query, &page),
+        Err(err) => json_error(StatusCode::INTERNAL_SERVER_ERROR, format!("Exception {err}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test, App};
+    use actix_web::http::StatusCode;
+    use actix_web::web::{scope, Data};
+    use crate::backend::api::listing::query_config;
+    use crate::backend::persistence::{ArticleId, Metadata};
+    use crate::backend::persistence::test_support::temp_repository;
+
+    #[actix_web::test]
+    async fn filtered_out_tag_is_an_empty_page() {
+        let repository = temp_repository("filtered_out_tag_is_an_empty_page");
+        let metadata = Metadata {
+            tags: ["diary".to_string()].into(),
+            ..Metadata::default()
+        };
+        repository.create_entry(ArticleId::new("a".to_string()), "content".to_string(), metadata, "token".to_string(), true).unwrap().unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(repository))
+                .service(scope("/api/tags").app_data(query_config()).service(super::articles))
+        ).await;
+
+        let req = test::TestRequest::get().uri("/api/tags/diary/articles?since=2999-01-01T00:00:00Z").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: serde_json::Value = test::read_body_json(res).await;
+        assert_eq!(body["total"], 0);
+
+        let req = test::TestRequest::get().uri("/api/tags/missing/articles").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    }
+}
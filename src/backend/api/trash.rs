@@ -0,0 +1,51 @@
+use actix_web::{HttpRequest, HttpResponse, Responder};
+use actix_web::{get, post};
+use actix_web::http::header::ETag;
+use actix_web::http::StatusCode;
+use actix_web::web::{Data, Path};
+use crate::backend::api::article::rejected;
+use crate::backend::api::auth::{json_unauthorized, unauthorized, validate_master_password, ValidateResult};
+use crate::backend::api::conditional::entity_tag;
+use crate::backend::api::schema::{json_error, ArticleDetail, Collection};
+use crate::backend::persistence::{ArticleId, ArticleRepository};
+use crate::extension::RespondPlainText;
+
+#[get("")]
+#[allow(clippy::future_not_send)]
+pub async fn list(req: HttpRequest, repository: Data<ArticleRepository>) -> impl Responder {
+    if validate_master_password(&req) != ValidateResult::RightBearer {
+        return json_unauthorized()
+    }
+
+    match repository.trash() {
+        Ok(mut entries) => {
+            // most recently deleted first
+            entries.sort_unstable_by(|a, b| (b.deleted_at, &b.id).cmp(&(a.deleted_at, &a.id)));
+            HttpResponse::build(StatusCode::OK)
+                .json(Collection::new(entries.into_iter().map(ArticleDetail::from).collect()))
+        }
+        Err(err) => json_error(StatusCode::INTERNAL_SERVER_ERROR, format!("Exception {err}")),
+    }
+}
+
+#[post("/{article_id}/restore")]
+#[allow(clippy::future_not_send)]
+pub async fn restore(path: Path<String>, req: HttpRequest, repository: Data<ArticleRepository>) -> impl Responder {
+    if validate_master_password(&req) != ValidateResult::RightBearer {
+        return unauthorized()
+    }
+
+    let article_id = ArticleId::new(path.into_inner());
+    match repository.undelete(&article_id) {
+        Ok(Ok(restored)) => {
+            HttpResponse::build(StatusCode::OK)
+                .insert_header(ETag(entity_tag(&restored)))
+                .respond_with_auto_charset(format!("OK, restored {article_id} from the trash."))
+        }
+        Ok(Err(rejection)) => rejected(rejection),
+        Err(err) => {
+            HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR)
+                .respond_with_auto_charset(format!("Exception {err}"))
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::sync::LazyLock;
+use ammonia::Builder;
+use anyhow::{Context, Result};
+use pulldown_cmark::{html, Options, Parser};
+
+pub const FRONT_MATTER_DELIMITER: &str = "+++";
+
+// Authors may write raw HTML in markdown, so the rendered HTML is cleaned of scripts, event handlers and the like.
+static SANITIZER: LazyLock<Builder<'static>> = LazyLock::new(|| {
+    let mut builder = Builder::default();
+    builder
+        // keeps the language of fenced code blocks for syntax highlighters
+        .add_tag_attributes("code", ["class"])
+        .attribute_filter(|element, attribute, value| {
+            if element == "code" && attribute == "class" && !value.starts_with("language-") {
+                None
+            } else {
+                Some(value.into())
+            }
+        });
+    builder
+});
+
+// CommonMark with tables; fenced code blocks are part of CommonMark itself.
+pub fn render_html(markdown: &str) -> String {
+    let parser = Parser::new_ext(markdown, Options::ENABLE_TABLES);
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, parser);
+
+    SANITIZER.clean(&unsafe_html).to_string()
+}
+
+pub fn render_document(title: &str, markdown: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n</head>\n<body>\n{body}</body>\n</html>\n",
+        title = ammonia::clean_text(title),
+        body = render_html(markdown),
+    )
+}
+
+// Splits a document that starts with a TOML front matter enclosed by `+++` lines into the front matter and the rest.
+// None if the document has no front matter.
+pub fn split_front_matter(text: &str) -> Result<Option<(&str, &str)>> {
+    let Some(rest) = text.strip_prefix(FRONT_MATTER_DELIMITER).and_then(|rest| rest.strip_prefix('\n')) else {
+        return Ok(None)
+    };
+
+    rest.split_once(&format!("\n{FRONT_MATTER_DELIMITER}\n"))
+        .or_else(|| rest.strip_suffix(&format!("\n{FRONT_MATTER_DELIMITER}")).map(|header| (header, "")))
+        .context("front matter is not closed")
+        .map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render_html;
+
+    #[test]
+    fn strips_scripts_and_event_handlers() {
+        let html = render_html("<script>alert(1)</script>\n\n<img src=\"a.png\" onerror=\"alert(1)\">\n\n[link](javascript:alert(1))");
+        assert!(!html.contains("<script"), "{html}");
+        assert!(!html.contains("alert"), "{html}");
+        assert!(!html.contains("onerror"), "{html}");
+        assert!(!html.contains("javascript:"), "{html}");
+        assert!(html.contains("<img src=\"a.png\""), "{html}");
+    }
+
+    #[test]
+    fn keeps_tables_and_code_languages() {
+        let html = render_html("| a | b |\n|---|---|\n| 1 | 2 |\n\n```rust\nfn main() {}\n```\n");
+        assert!(html.contains("<table>"), "{html}");
+        assert!(html.contains("<td>1</td>"), "{html}");
+        assert!(html.contains("<code class=\"language-rust\">"), "{html}");
+
+        // other classes could restyle the page
+        assert_eq!(render_html("<code class=\"banner\">x</code>"), "<p><code>x</code></p>\n");
+    }
+}
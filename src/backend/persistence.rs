@@ -1,145 +1,665 @@
-use std::collections::HashMap;
+pub mod blob;
+pub mod directory;
+pub mod json_file;
+pub mod page;
+pub mod sqlite;
+mod tag_index;
+#[cfg(test)]
+pub mod test_support;
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fmt::{Debug, Display, Formatter};
-use std::fs::File;
-use std::io::{BufReader, BufWriter, Read, Write};
+use std::fs::{self, File};
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
-use anyhow::{Context, Result};
-use chrono::{DateTime, Local};
-use log::{error, info};
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Local};
+use log::{info, warn};
 use serde::{Serialize, Deserialize};
+use crate::backend::persistence::blob::BlobStore;
+use crate::backend::persistence::page::{page_of, ArticlePage, PageQuery};
+use crate::backend::persistence::tag_index::TagIndex;
+
+// canonical paths of the files opened by live storages in this process
+static OPENED_PATHS: LazyLock<Mutex<HashSet<PathBuf>>> = LazyLock::new(Mutex::default);
+
+// Two storages that share a file would overwrite each other's changes, since each of them
+// has its own lock. Holding this guard makes the second attempt to open the file fail instead.
+struct PathGuard(PathBuf);
+
+impl PathGuard {
+    fn acquire(path: &Path) -> Result<Self> {
+        let path = path.canonicalize().context("canonicalize path")?;
+        if !OPENED_PATHS.lock().unwrap().insert(path.clone()) {
+            bail!("{path} is already opened by another storage", path = path.display());
+        }
+
+        Ok(Self(path))
+    }
+}
+
+impl Drop for PathGuard {
+    fn drop(&mut self) {
+        OPENED_PATHS.lock().unwrap().remove(&self.0);
+    }
+}
+
+// Writes the content to a sibling temporary file, then renames it over the original.
+// Readers (and a restarted process after a crash) see either the old or the new content, never a mix.
+fn write_atomically(path: &Path, content: &[u8]) -> Result<()> {
+    let mut temp_name = path.file_name().context("path must point to a file")?.to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+
+    {
+        let mut file = File::create(&temp_path).context("create temporary file")?;
+        file.write_all(content)?;
+        file.sync_all().context("sync temporary file")?;
+    }
+
+    fs::rename(&temp_path, path).context("replace file")?;
+
+    // the rename itself is durable only after the directory entry is flushed
+    #[cfg(unix)]
+    {
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        File::open(parent).and_then(|dir| dir.sync_all()).context("sync directory")?;
+    }
+
+    Ok(())
+}
+
+// Told of an article that changed without going through the storage, or of its id with None if it disappeared.
+pub type ChangeListener = Box<dyn Fn(&ArticleId, Option<&Article>) + Send + Sync>;
+
+pub trait ArticleStorage: Send + Sync {
+    fn get(&self, article_id: &ArticleId) -> Result<Option<Article>>;
+
+    fn put(&self, article: Article) -> Result<()>;
+
+    fn remove(&self, article_id: &ArticleId) -> Result<()>;
+
+    fn list(&self) -> Result<Vec<Article>>;
+
+    // Storages should override this to filter, sort and limit without reading every article.
+    fn list_page(&self, query: &PageQuery) -> Result<ArticlePage> {
+        Ok(page_of(&self.list()?, query))
+    }
+
+    fn exists(&self, article_id: &ArticleId) -> Result<bool>;
+
+    // Returned in ascending order of their numbers. Removing an article removes its revisions too.
+    fn revisions(&self, article_id: &ArticleId) -> Result<Vec<Revision>>;
+
+    fn add_revision(&self, article_id: &ArticleId, revision: Revision) -> Result<()>;
+
+    // Stores the article along with new revisions of it. Storages should override this to write them at once;
+    // otherwise the revisions go first, so that a crash in between cannot leave a stored revision out of the history.
+    fn put_with_revisions(&self, article: Article, revisions: Vec<Revision>) -> Result<()> {
+        for revision in revisions {
+            self.add_revision(&article.id, revision)?;
+        }
+        self.put(article)
+    }
+
+    // Stores many articles at once, each with its revision history. Storages may override this to do it in a single transaction.
+    fn import(&self, articles: Vec<(Article, Vec<Revision>)>) -> Result<()> {
+        articles.into_iter().try_for_each(|(article, revisions)| self.put_with_revisions(article, revisions))
+    }
+
+    // Persists mutations that the storage has buffered, if any.
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
+    // Storages whose articles can be changed from outside, such as the directory one, call the listener
+    // whenever they notice a change. Only one listener is kept.
+    fn watch(&self, _listener: ChangeListener) {}
+}
 
 pub struct ArticleRepository {
-    path: PathBuf,
-    lock: RwLock<()>
+    storage: Box<dyn ArticleStorage>,
+    blobs: BlobStore,
+    // Built from the storage on start, then kept in step with it by every mutation, and by the storage itself
+    // for the files edited by hand in the directory storage once it reads them again.
+    tags: Arc<RwLock<TagIndex>>,
+    // Held from inspecting the current entry to storing the new one, so concurrent mutations
+    // cannot lose each other's changes. Reads go straight to the storage.
+    write_lock: Mutex<()>,
 }
 
 impl ArticleRepository {
-    fn create_default_file_if_absent(path: impl AsRef<Path>) {
-        if !path.as_ref().exists() {
-            let mut file = File::options().write(true).read(true).create(true).open(path.as_ref()).unwrap();
-            write!(
-                &mut (file),
-                "{default_json}",
-                default_json = serde_json::to_string(&FileScheme::empty()).unwrap()
-            ).unwrap();
-        }
+    pub fn new(storage: Box<dyn ArticleStorage>, blobs: BlobStore) -> Result<Self> {
+        let tags = Arc::new(RwLock::new(TagIndex::build(&storage.list()?)));
+        let index = Arc::clone(&tags);
+        storage.watch(Box::new(move |article_id, article| {
+            let mut index = index.write().unwrap();
+            match article {
+                Some(article) => index.update(article),
+                None => index.remove(article_id),
+            }
+        }));
+
+        Ok(Self {
+            storage,
+            blobs,
+            tags,
+            write_lock: Mutex::new(()),
+        })
     }
 
-    // TODO: 誤って同じパスに対してこのメソッドを二回以上呼ぶと破滅する
-    pub fn new(path: impl AsRef<Path>) -> Self {
-        Self::create_default_file_if_absent(path.as_ref());
+    // Unless `publish` is set, the article is created as a draft.
+    pub fn create_entry(
+        &self,
+        article_id: ArticleId,
+        article_content: String,
+        metadata: Metadata,
+        token_id: String,
+        publish: bool,
+    ) -> Result<Result<Article, Rejection>> {
+        info!("calling create_entry");
+        let _lock = self.write_lock.lock().unwrap();
+        match self.storage.get(&article_id)? {
+            Some(existing) if existing.deleted_at.is_some() => return Ok(Err(Rejection::InTrash)),
+            Some(_) => return Ok(Err(Rejection::AlreadyExists)),
+            None => {}
+        }
 
-        Self {
-            path: path.as_ref().to_path_buf(),
-            lock: RwLock::new(())
+        let mut article = Article::new(article_id, article_content, metadata);
+        if publish {
+            article = article.published();
+        }
+        let revision = article.to_revision(Some(token_id));
+        self.store_with_revisions(article, vec![revision]).map(Ok)
+    }
+
+    // `precondition` is evaluated against the current entry inside the transaction.
+    // The metadata is kept as it is unless a new one is given.
+    pub fn update_entry(
+        &self,
+        article_id: &ArticleId,
+        article_content: String,
+        metadata: Option<Metadata>,
+        token_id: String,
+        precondition: impl FnOnce(&Article) -> bool,
+    ) -> Result<Result<Article, Rejection>> {
+        info!("calling update_entry");
+        let _lock = self.write_lock.lock().unwrap();
+        let Some(current) = self.get_live(article_id)? else {
+            return Ok(Err(Rejection::NotFound))
+        };
+        if !precondition(&current) {
+            return Ok(Err(Rejection::PreconditionFailed))
         }
+
+        self.update_locked(current, article_content, metadata, token_id).map(Ok)
     }
 
-    fn get_write_handle(&self) -> (Result<File>, RwLockWriteGuard<'_, ()>) {
-        (File::options().write(true).open(&self.path).context("open file"), self.lock.write().unwrap())
+    // Makes the content of the given revision the current one, as a new revision. The metadata is left as it is.
+    pub fn restore_revision(
+        &self,
+        article_id: &ArticleId,
+        number: u64,
+        token_id: String,
+        precondition: impl FnOnce(&Article) -> bool,
+    ) -> Result<Result<Article, Rejection>> {
+        info!("calling restore_revision");
+        let _lock = self.write_lock.lock().unwrap();
+        let Some(current) = self.get_live(article_id)? else {
+            return Ok(Err(Rejection::NotFound))
+        };
+        let Some(revision) = self.revisions_of(&current)?.into_iter().find(|revision| revision.number == number) else {
+            return Ok(Err(Rejection::NotFound))
+        };
+        if !precondition(&current) {
+            return Ok(Err(Rejection::PreconditionFailed))
+        }
+
+        self.update_locked(current, revision.content, None, token_id).map(Ok)
     }
 
-    fn get_read_handle(&self) -> (Result<File>, RwLockReadGuard<'_, ()>) {
-        (File::options().read(true).open(&self.path).context("open file"), self.lock.read().unwrap())
+    // callers must hold `write_lock`
+    fn update_locked(&self, mut current: Article, article_content: String, metadata: Option<Metadata>, token_id: String) -> Result<Article> {
+        // keep the current content so that it can be restored
+        let mut revisions = vec![];
+        if let Some(unrecorded) = unrecorded_revision(&current, &self.storage.revisions(&current.id)?) {
+            current.revision = unrecorded.number;
+            revisions.push(unrecorded);
+        }
+
+        let mut article = current.with_content(article_content);
+        if let Some(metadata) = metadata {
+            article.metadata = metadata;
+        }
+        revisions.push(article.to_revision(Some(token_id)));
+        self.store_with_revisions(article, revisions)
     }
 
-    pub async fn set_entry(&self, article_id: ArticleId, article_content: String) -> Result<()> {
-        info!("calling add_entry");
-        let mut a = self.parse_file_as_json()?;
-        info!("parsed");
-        let (file, _lock) = self.get_write_handle();
-        let file = file?;
+    // None if the article does not exist
+    pub fn revisions(&self, article_id: &ArticleId) -> Result<Option<Vec<Revision>>> {
+        let Some(current) = self.get_live(article_id)? else {
+            return Ok(None)
+        };
 
-        {
-            (&mut a.data).insert(article_id.clone(), Article {
-                created_at: Local::now(),
-                // visible: false,
-                content: article_content,
-                id: article_id,
-            });
-            info!("modified");
+        self.revisions_of(&current).map(Some)
+    }
+
+    // includes the current content even if it has not been recorded yet, under the number it will be recorded as
+    fn revisions_of(&self, article: &Article) -> Result<Vec<Revision>> {
+        let mut revisions = self.storage.revisions(&article.id)?;
+        if let Some(unrecorded) = unrecorded_revision(article, &revisions) {
+            revisions.push(unrecorded);
         }
 
-        serde_json::to_writer(file, &a)?;
-        info!("wrote");
-        Ok(())
+        Ok(revisions)
     }
 
-    pub async fn read_snapshot(&self, article_id: &ArticleId) -> Result<Article> {
-        info!("calling read");
-        let a = self.parse_file_as_json()?;
-        a.data.get(article_id).cloned().context(format!("read_snapshot: failed to get {article_id:?}"))
+    pub fn read_snapshot(&self, article_id: &ArticleId) -> Result<Option<Article>> {
+        self.get_live(article_id)
     }
 
-    pub async fn exists(&self, article_id: &ArticleId) -> Result<bool> {
-        info!("calling exists");
-        let a = self.parse_file_as_json()?;
-        Ok(a.data.contains_key(article_id))
+    // articles in the trash are treated as if they did not exist
+    fn get_live(&self, article_id: &ArticleId) -> Result<Option<Article>> {
+        Ok(self.storage.get(article_id)?.filter(|article| article.deleted_at.is_none()).map(|article| article.settled(Local::now())))
     }
 
-    pub async fn remove(&self, article_id: &ArticleId) -> Result<()> {
+    // Moves the article to the trash. It is erased by `purge` once the retention period has passed.
+    pub fn remove(&self, article_id: &ArticleId, precondition: impl FnOnce(&Article) -> bool) -> Result<Result<(), Rejection>> {
         info!("calling remove");
-        let mut a = self.parse_file_as_json()?;
-        info!("parsed");
-        let (file, _lock) = self.get_write_handle();
-        let file = file?;
+        let _lock = self.write_lock.lock().unwrap();
+        let Some(current) = self.get_live(article_id)? else {
+            return Ok(Err(Rejection::NotFound))
+        };
+        if !precondition(&current) {
+            return Ok(Err(Rejection::PreconditionFailed))
+        }
 
-        {
-            (&mut a.data).remove(article_id);
-            info!("modified");
+        self.store(Article {
+            deleted_at: Some(Local::now()),
+            ..current
+        })?;
+        Ok(Ok(()))
+    }
+
+    // Publishing an article that is already visible keeps its `published_at`.
+    pub fn publish(&self, article_id: &ArticleId) -> Result<Result<Article, Rejection>> {
+        info!("calling publish");
+        self.set_visibility(article_id, Article::published)
+    }
+
+    // The article is hidden until `publish_at`, then becomes visible without further requests.
+    pub fn schedule(&self, article_id: &ArticleId, publish_at: DateTime<Local>) -> Result<Result<Article, Rejection>> {
+        info!("calling schedule");
+        self.set_visibility(article_id, |article| Article {
+            visible: false,
+            publish_at: Some(publish_at),
+            ..article
+        }.settled(Local::now()))
+    }
+
+    // also cancels the schedule, if any
+    pub fn unpublish(&self, article_id: &ArticleId) -> Result<Result<Article, Rejection>> {
+        info!("calling unpublish");
+        self.set_visibility(article_id, |article| Article {
+            visible: false,
+            publish_at: None,
+            ..article
+        })
+    }
+
+    fn set_visibility(&self, article_id: &ArticleId, f: impl FnOnce(Article) -> Article) -> Result<Result<Article, Rejection>> {
+        let _lock = self.write_lock.lock().unwrap();
+        let Some(current) = self.get_live(article_id)? else {
+            return Ok(Err(Rejection::NotFound))
+        };
+
+        self.store(f(current)).map(Ok)
+    }
+
+    pub fn undelete(&self, article_id: &ArticleId) -> Result<Result<Article, Rejection>> {
+        info!("calling undelete");
+        let _lock = self.write_lock.lock().unwrap();
+        let Some(article) = self.storage.get(article_id)?.filter(|article| article.deleted_at.is_some()).map(|article| article.settled(Local::now())) else {
+            return Ok(Err(Rejection::NotFound))
+        };
+
+        self.store(Article {
+            deleted_at: None,
+            ..article
+        }).map(Ok)
+    }
+
+    // Leaves `updated_at` as it is, since the content does not change, but bumps `modified_at` like every write.
+    pub fn add_attachment(&self, article_id: &ArticleId, name: String, content_type: String, content: &[u8]) -> Result<Result<Attachment, Rejection>> {
+        info!("calling add_attachment");
+        let _lock = self.write_lock.lock().unwrap();
+        let Some(mut article) = self.get_live(article_id)? else {
+            return Ok(Err(Rejection::NotFound))
+        };
+        if article.attachments.contains_key(&name) {
+            return Ok(Err(Rejection::AlreadyExists))
         }
 
-        let json = serde_json::to_string(&a)?;
-        write!(
-            &mut BufWriter::new(&file),
-            "{json}"
-        )?;
+        let attachment = Attachment {
+            content_type,
+            size: content.len() as u64,
+            sha256: self.blobs.put(content)?,
+            uploaded_at: Local::now(),
+        };
+        article.attachments.insert(name, attachment.clone());
+        self.store(article)?;
+        Ok(Ok(attachment))
+    }
+
+    pub fn read_attachment(&self, attachment: &Attachment) -> Result<Option<Vec<u8>>> {
+        self.blobs.get(&attachment.sha256)
+    }
+
+    pub fn remove_attachment(&self, article_id: &ArticleId, name: &str) -> Result<Result<(), Rejection>> {
+        info!("calling remove_attachment");
+        let _lock = self.write_lock.lock().unwrap();
+        let Some(mut article) = self.get_live(article_id)? else {
+            return Ok(Err(Rejection::NotFound))
+        };
+        let Some(removed) = article.attachments.remove(name) else {
+            return Ok(Err(Rejection::NotFound))
+        };
+
+        self.store(article)?;
+        self.collect_garbage([removed.sha256])?;
+        Ok(Ok(()))
+    }
 
-        // You must truncate, or you will be fired
-        file.set_len(json.len() as u64)?;
+    // Removes the blobs that no article refers to anymore. Callers must hold `write_lock`.
+    fn collect_garbage(&self, candidates: impl IntoIterator<Item = String>) -> Result<()> {
+        let mut candidates: HashSet<_> = candidates.into_iter().collect();
+        if candidates.is_empty() {
+            return Ok(())
+        }
+
+        for article in self.storage.list()? {
+            for attachment in article.attachments.values() {
+                candidates.remove(&attachment.sha256);
+            }
+        }
+        for hash in candidates {
+            self.blobs.remove(&hash)?;
+        }
 
-        info!("wrote");
         Ok(())
     }
 
-    pub(in crate::backend) fn parse_file_as_json(&self) -> Result<FileScheme> {
-        let (file, _lock) = self.get_read_handle();
-        let mut read_all = BufReader::new(file?);
-        let mut buf = vec![];
-        read_all.read_to_end(&mut buf).context("verify file")?;
-        let got = String::from_utf8(buf).context("utf8 verify")?;
-        info!("file JSON: {got}", got = &got);
+    // Every write goes through this and `erase`, so that the tag index follows the storage and `modified_at` is bumped.
+    // Returns the article as it was stored. Callers must hold `write_lock`.
+    fn store(&self, article: Article) -> Result<Article> {
+        let article = article.modified(Local::now());
+        self.storage.put(article.clone())?;
+        self.tags.write().unwrap().update(&article);
+        Ok(article)
+    }
+
+    fn store_with_revisions(&self, article: Article, revisions: Vec<Revision>) -> Result<Article> {
+        let article = article.modified(Local::now());
+        self.storage.put_with_revisions(article.clone(), revisions)?;
+        self.tags.write().unwrap().update(&article);
+        Ok(article)
+    }
+
+    fn erase(&self, article_id: &ArticleId) -> Result<()> {
+        self.storage.remove(article_id)?;
+        self.tags.write().unwrap().remove(article_id);
+        Ok(())
+    }
+
+    // Tags in ascending order with the number of articles that have them.
+    // Drafts are counted unless `visible_at` is set, as in `PageQuery`.
+    pub fn tags(&self, visible_at: Option<DateTime<Local>>) -> Vec<(String, usize)> {
+        self.tags.read().unwrap().counts(visible_at)
+    }
+
+    // whether any article has the tag, counting drafts as in `tags`
+    pub fn has_tag(&self, tag: &str, visible_at: Option<DateTime<Local>>) -> bool {
+        self.tags.read().unwrap().contains(tag, visible_at)
+    }
+
+    // Looks up only the articles the index has for the tag.
+    pub fn tagged(&self, tag: &str, query: &PageQuery) -> Result<ArticlePage> {
+        let article_ids = self.tags.read().unwrap().articles(tag, query.visible_at);
+        let mut articles = vec![];
+        for article_id in article_ids {
+            // the file may have been edited by hand since it was indexed
+            if let Some(article) = self.storage.get(&article_id)?.filter(|article| article.metadata.tags.contains(tag)) {
+                articles.push(article);
+            }
+        }
+
+        Ok(Self::settle_page(page_of(&articles, query)))
+    }
+
+    pub fn list_page(&self, query: &PageQuery) -> Result<ArticlePage> {
+        Ok(Self::settle_page(self.storage.list_page(query)?))
+    }
+
+    fn settle_page(page: ArticlePage) -> ArticlePage {
+        let now = Local::now();
+        ArticlePage {
+            articles: page.articles.into_iter().map(|article| article.settled(now)).collect(),
+            ..page
+        }
+    }
+
+    pub fn trash(&self) -> Result<Vec<Article>> {
+        let now = Local::now();
+        Ok(self.storage.list()?.into_iter().filter(|article| article.deleted_at.is_some()).map(|article| article.settled(now)).collect())
+    }
+
+    // Permanently erases the articles that have been in the trash longer than `retention`. Returns how many were erased.
+    pub fn purge(&self, retention: Duration) -> Result<usize> {
+        let _lock = self.write_lock.lock().unwrap();
+        let threshold = Local::now() - retention;
+        let mut purged = 0;
+        let mut unreferenced = vec![];
+        for article in self.storage.list()? {
+            if article.deleted_at.is_some_and(|deleted_at| deleted_at < threshold) {
+                info!("purging {id}", id = &article.id);
+                self.erase(&article.id)?;
+                unreferenced.extend(article.attachments.into_values().map(|attachment| attachment.sha256));
+                purged += 1;
+            }
+        }
+
+        self.collect_garbage(unreferenced)?;
+        Ok(purged)
+    }
+
+    // Articles that already exist are left untouched. Returns how many articles were imported.
+    pub fn import(&self, articles: Vec<(Article, Vec<Revision>)>) -> Result<usize> {
+        let _lock = self.write_lock.lock().unwrap();
+        let mut fresh = vec![];
+        for (article, revisions) in articles {
+            if self.storage.exists(&article.id)? {
+                warn!("import: skipping {id} as it already exists", id = &article.id);
+            } else {
+                fresh.push((article, revisions));
+            }
+        }
+
+        let imported = fresh.len();
+        let stored: Vec<_> = fresh.iter().map(|(article, _)| article.clone()).collect();
+        self.storage.import(fresh)?;
+        let mut tags = self.tags.write().unwrap();
+        for article in &stored {
+            tags.update(article);
+        }
+        drop(tags);
+        Ok(imported)
+    }
+
+    pub fn flush(&self) -> Result<()> {
+        self.storage.flush()
+    }
+}
 
-        serde_json::from_str(got.as_str()).map_err(|e| {
-            error!("{e}", e = &e);
-            e
-        }).context("reading json file")
+// The current content is missing from the history if the article was stored before revisions were recorded,
+// or if its file in the directory storage has been edited by hand since the last write.
+// Returns the revision it should be recorded as, numbered after every stored one.
+fn unrecorded_revision(current: &Article, revisions: &[Revision]) -> Option<Revision> {
+    match revisions.last() {
+        None => Some(current.to_revision(None)),
+        Some(latest) if latest.content != current.content => Some(Revision {
+            number: latest.number.max(current.revision) + 1,
+            ..current.to_revision(None)
+        }),
+        Some(_) => None,
     }
 }
 
-#[derive(Serialize, Deserialize)]
-pub(in crate::backend) struct FileScheme {
-    // TODO: この形式で永続化されるのは好みではないが、実装の速度を優先して形式の調整は凍結する
-    pub(in crate::backend) data: HashMap<ArticleId, Article>
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Rejection {
+    AlreadyExists,
+    // the id is taken by an article in the trash
+    InTrash,
+    NotFound,
+    PreconditionFailed,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Article {
+    pub created_at: DateTime<Local>,
+    pub updated_at: DateTime<Local>,
+    // when anything about the article last changed, such as its visibility or attachments; `updated_at` follows the content only
+    pub modified_at: DateTime<Local>,
+    // starts from 1 and is incremented on every write
+    pub revision: u64,
+    pub content: String,
+    pub id: ArticleId,
+    pub metadata: Metadata,
+    // drafts are visible only to authorized readers
+    pub visible: bool,
+    // when the article was last made visible; kept while it is unpublished
+    pub published_at: Option<DateTime<Local>>,
+    // when a draft is scheduled to become visible
+    pub publish_at: Option<DateTime<Local>>,
+    // keyed by file name
+    pub attachments: BTreeMap<String, Attachment>,
+    // set while the article is in the trash
+    pub deleted_at: Option<DateTime<Local>>,
 }
 
-impl FileScheme {
-    fn empty() -> Self {
+impl Article {
+    fn new(article_id: ArticleId, article_content: String, metadata: Metadata) -> Self {
+        let now = Local::now();
+        Self {
+            created_at: now,
+            updated_at: now,
+            modified_at: now,
+            revision: 1,
+            content: article_content,
+            id: article_id,
+            metadata,
+            visible: false,
+            published_at: None,
+            publish_at: None,
+            attachments: BTreeMap::new(),
+            deleted_at: None,
+        }
+    }
+
+    fn published(self) -> Self {
+        if self.visible {
+            return self
+        }
+
+        Self {
+            visible: true,
+            published_at: Some(Local::now()),
+            publish_at: None,
+            ..self
+        }
+    }
+
+    // Makes a scheduled article visible if its time has come. Stored articles are brought up to date
+    // by this whenever they are read, so the schedule needs no background job.
+    fn settled(self, now: DateTime<Local>) -> Self {
+        match self.publish_at {
+            Some(publish_at) if publish_at <= now => Self {
+                visible: true,
+                published_at: Some(publish_at),
+                publish_at: None,
+                ..self
+            },
+            _ => self,
+        }
+    }
+
+    fn modified(self, now: DateTime<Local>) -> Self {
+        Self {
+            modified_at: now,
+            ..self
+        }
+    }
+
+    // The representations that show more than the content change along with any of it, so they are validated by this
+    // rather than by `updated_at`. A scheduled article changes when it becomes visible, which takes no write.
+    pub fn last_modified(&self) -> DateTime<Local> {
+        self.published_at.map_or(self.modified_at, |published_at| published_at.max(self.modified_at))
+    }
+
+    // keeps the original publication date
+    fn with_content(self, article_content: String) -> Self {
         Self {
-            data: HashMap::new(),
+            updated_at: Local::now(),
+            revision: self.revision + 1,
+            content: article_content,
+            ..self
+        }
+    }
+
+    fn to_revision(&self, token_id: Option<String>) -> Revision {
+        Revision {
+            number: self.revision,
+            created_at: self.updated_at,
+            token_id,
+            content: self.content.clone(),
         }
     }
 }
 
+// Written by the author along with the content. Every field may be left out.
+#[derive(Deserialize, Serialize, Clone, Default, Eq, PartialEq, Debug)]
+#[serde(default)]
+pub struct Metadata {
+    pub title: Option<String>,
+    // a short excerpt shown in listings
+    pub summary: Option<String>,
+    pub tags: BTreeSet<String>,
+    pub author: Option<String>,
+}
+
 #[derive(Deserialize, Serialize, Clone)]
-pub struct Article {
+pub struct Attachment {
+    pub content_type: String,
+    pub size: u64,
+    // hex-encoded; also locates the body in the blob store
+    pub sha256: String,
+    pub uploaded_at: DateTime<Local>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Revision {
+    pub number: u64,
     pub created_at: DateTime<Local>,
+    // identifies the bearer token the revision was written with;
+    // None for the revisions that were not written through the API, such as the ones from before the history was recorded
+    pub token_id: Option<String>,
     pub content: String,
-    pub id: ArticleId
 }
 
-#[derive(Hash, Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[derive(Hash, Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Serialize, Deserialize)]
 pub struct ArticleId(String);
 
 impl ArticleId {
@@ -152,4 +672,148 @@ impl Display for ArticleId {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         Display::fmt(&self.0, f)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use crate::backend::persistence::blob::BlobStore;
+    use crate::backend::persistence::directory::DirectoryStorage;
+    use crate::backend::persistence::json_file::{FlushPolicy, JsonFileStorage};
+    use crate::backend::persistence::test_support::{temp_dir, temp_repository};
+    use super::{ArticleId, ArticleRepository, Metadata};
+
+    fn directory_repository(directory: &Path) -> ArticleRepository {
+        let storage = DirectoryStorage::open(directory.join("articles")).unwrap();
+        ArticleRepository::new(Box::new(storage), BlobStore::open(directory.join("attachments")).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn keeps_hand_edits_in_history() {
+        let directory = temp_dir("keeps_hand_edits_in_history");
+        let repository = directory_repository(&directory);
+        let article_id = ArticleId::new("a".to_string());
+        repository.create_entry(article_id.clone(), "first".to_string(), Metadata::default(), "token".to_string(), true).unwrap().unwrap();
+
+        let path = directory.join("articles").join("a.md");
+        fs::write(&path, fs::read_to_string(&path).unwrap().replace("first", "edited by hand")).unwrap();
+        let history = |repository: &ArticleRepository| -> Vec<(u64, String)> {
+            repository.revisions(&article_id).unwrap().unwrap().into_iter().map(|revision| (revision.number, revision.content)).collect()
+        };
+        assert_eq!(history(&repository), [(1, "first".to_string()), (2, "edited by hand".to_string())]);
+
+        let updated = repository.update_entry(&article_id, "second".to_string(), None, "token".to_string(), |_| true).unwrap().unwrap();
+        assert_eq!(updated.revision, 3);
+        assert_eq!(history(&repository), [(1, "first".to_string()), (2, "edited by hand".to_string()), (3, "second".to_string())]);
+    }
+
+    #[test]
+    fn import_keeps_revisions() {
+        let directory = temp_dir("import_keeps_revisions");
+        let article_id = ArticleId::new("a".to_string());
+        {
+            let source = ArticleRepository::new(
+                Box::new(JsonFileStorage::open(directory.join("index.json"), FlushPolicy::OnWrite).unwrap()),
+                BlobStore::open(directory.join("attachments")).unwrap(),
+            ).unwrap();
+            source.create_entry(article_id.clone(), "first".to_string(), Metadata::default(), "token".to_string(), true).unwrap().unwrap();
+            source.update_entry(&article_id, "second".to_string(), None, "token".to_string(), |_| true).unwrap().unwrap();
+        }
+
+        let repository = directory_repository(&directory);
+        let imported = repository.import(JsonFileStorage::read_articles(directory.join("index.json")).unwrap()).unwrap();
+        assert_eq!(imported, 1);
+        let history: Vec<_> = repository.storage.revisions(&article_id).unwrap().into_iter().map(|revision| (revision.number, revision.content)).collect();
+        assert_eq!(history, [(1, "first".to_string()), (2, "second".to_string())]);
+    }
+
+    #[test]
+    fn unparsable_files_are_skipped() {
+        let directory = temp_dir("unparsable_files_are_skipped");
+        directory_repository(&directory)
+            .create_entry(ArticleId::new("a".to_string()), "content".to_string(), Metadata::default(), "token".to_string(), true).unwrap().unwrap();
+        fs::write(directory.join("articles").join("broken.md"), "+++\nnot toml ===\n+++\n").unwrap();
+
+        // the repository lists every article on start
+        let repository = directory_repository(&directory);
+        let listed: Vec<_> = repository.storage.list().unwrap().into_iter().map(|article| article.id.to_string()).collect();
+        assert_eq!(listed, ["a"]);
+        assert!(repository.read_snapshot(&ArticleId::new("broken".to_string())).is_err());
+    }
+
+    #[test]
+    fn hand_edited_tags_are_indexed_once_read() {
+        let directory = temp_dir("hand_edited_tags_are_indexed_once_read");
+        let repository = directory_repository(&directory);
+        let article_id = ArticleId::new("a".to_string());
+        repository.create_entry(article_id.clone(), "content".to_string(), Metadata::default(), "token".to_string(), true).unwrap().unwrap();
+        assert!(repository.tags(None).is_empty());
+
+        let path = directory.join("articles").join("a.md");
+        fs::write(&path, fs::read_to_string(&path).unwrap().replacen("+++\n", "+++\ntags = [\"diary\"]\n", 1)).unwrap();
+        repository.read_snapshot(&article_id).unwrap().unwrap();
+        assert_eq!(repository.tags(None), [("diary".to_string(), 1)]);
+
+        fs::remove_file(&path).unwrap();
+        assert!(repository.read_snapshot(&article_id).unwrap().is_none());
+        assert!(repository.tags(None).is_empty());
+    }
+
+    #[test]
+    fn hand_deleted_files_are_unindexed_by_listing() {
+        let directory = temp_dir("hand_deleted_files_are_unindexed_by_listing");
+        let repository = directory_repository(&directory);
+        let metadata = Metadata {
+            tags: ["t".to_string()].into(),
+            ..Metadata::default()
+        };
+        repository.create_entry(ArticleId::new("a".to_string()), "content".to_string(), metadata, "token".to_string(), true).unwrap().unwrap();
+        assert_eq!(repository.tags(None), [("t".to_string(), 1)]);
+
+        fs::remove_file(directory.join("articles").join("a.md")).unwrap();
+        repository.storage.list().unwrap();
+        assert!(repository.tags(None).is_empty());
+    }
+
+    // The JSON and HTML representations list the attachments, so their Last-Modified must follow them.
+    // `updated_at` follows the content only.
+    fn assert_attachments_bump_only_modified_at(repository: &ArticleRepository) {
+        let article_id = ArticleId::new("a".to_string());
+        let created = repository.create_entry(article_id.clone(), "content".to_string(), Metadata::default(), "token".to_string(), true).unwrap().unwrap();
+        let read = || repository.read_snapshot(&article_id).unwrap().unwrap();
+
+        repository.add_attachment(&article_id, "a.txt".to_string(), "text/plain".to_string(), b"data").unwrap().unwrap();
+        let uploaded = read();
+        assert!(uploaded.modified_at > created.modified_at);
+
+        repository.remove_attachment(&article_id, "a.txt").unwrap().unwrap();
+        repository.unpublish(&article_id).unwrap().unwrap();
+        let current = read();
+        assert!(current.modified_at > uploaded.modified_at);
+        assert_eq!(current.last_modified(), current.modified_at);
+        assert_eq!(current.updated_at, created.updated_at);
+        assert_eq!(current.revision, 1);
+    }
+
+    #[test]
+    fn attachments_bump_modified_at() {
+        assert_attachments_bump_only_modified_at(&temp_repository("attachments_bump_modified_at"));
+    }
+
+    // the storage's own writes must not be taken for hand edits, before or after a restart
+    #[test]
+    fn attachments_bump_modified_at_in_directory() {
+        let directory = temp_dir("attachments_bump_modified_at_in_directory");
+        let article_id = ArticleId::new("a".to_string());
+        let before_restart = {
+            let repository = directory_repository(&directory);
+            assert_attachments_bump_only_modified_at(&repository);
+            repository.read_snapshot(&article_id).unwrap().unwrap()
+        };
+
+        let after_restart = directory_repository(&directory).read_snapshot(&article_id).unwrap().unwrap();
+        assert_eq!(after_restart.updated_at, before_restart.updated_at);
+        assert_eq!(after_restart.revision, 1);
+    }
+}
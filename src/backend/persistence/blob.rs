@@ -0,0 +1,51 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use crate::backend::persistence::{write_atomically, PathGuard};
+
+// Keeps attachment bodies as `<sha256>` files in a directory, independently of the article storage.
+// Identical uploads share a single file.
+pub struct BlobStore {
+    directory: PathBuf,
+    _guard: PathGuard,
+}
+
+impl BlobStore {
+    pub fn open(directory: impl AsRef<Path>) -> Result<Self> {
+        fs::create_dir_all(directory.as_ref()).context("create directory")?;
+        let guard = PathGuard::acquire(directory.as_ref())?;
+
+        Ok(Self {
+            directory: directory.as_ref().to_path_buf(),
+            _guard: guard,
+        })
+    }
+
+    // Returns the hex-encoded hash that identifies the blob.
+    pub fn put(&self, content: &[u8]) -> Result<String> {
+        let hash = format!("{:x}", Sha256::digest(content));
+        let path = self.directory.join(&hash);
+        if !path.exists() {
+            write_atomically(&path, content).context("write blob")?;
+        }
+
+        Ok(hash)
+    }
+
+    pub fn get(&self, hash: &str) -> Result<Option<Vec<u8>>> {
+        match fs::read(self.directory.join(hash)) {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).context("read blob"),
+        }
+    }
+
+    pub fn remove(&self, hash: &str) -> Result<()> {
+        match fs::remove_file(self.directory.join(hash)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e).context("remove blob"),
+            _ => Ok(()),
+        }
+    }
+}
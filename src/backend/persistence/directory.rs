@@ -0,0 +1,302 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Local};
+use log::{info, warn};
+use serde::{Serialize, Deserialize};
+use crate::backend::markdown::{split_front_matter, FRONT_MATTER_DELIMITER};
+use crate::backend::persistence::{write_atomically, Article, ArticleId, ArticleStorage, Attachment, ChangeListener, Metadata, PathGuard, Revision};
+
+const EXTENSION: &str = "md";
+// cannot collide with an article, as ids never start with a dot
+const REVISIONS_DIRECTORY: &str = ".revisions";
+
+// Stores each article as `<ArticleId>.md`, with its metadata in a TOML front matter:
+//
+// +++
+// created_at = "2022-06-20T12:34:56.789+09:00"
+// updated_at = "2022-06-20T12:34:56.789+09:00"
+// modified_at = "2022-06-20T12:34:56.789+09:00"
+// revision = 1
+// visible = true
+// published_at = "2022-06-20T12:34:56.789+09:00"
+// title = "Hello"
+// tags = ["diary"]
+// +++
+// content...
+//
+// Files may be created or edited by hand while the server is running; they are re-read whenever
+// their modification time changes. A file whose content differs from its latest revision has been edited by hand,
+// and is treated as updated when it was last modified.
+// The revision history of each article is kept apart in `.revisions/<ArticleId>.json`.
+pub struct DirectoryStorage {
+    directory: PathBuf,
+    // parsed files, keyed by the modification time they had when they were read
+    cache: Mutex<HashMap<ArticleId, (SystemTime, Article)>>,
+    // told of every file that is parsed again or found removed
+    listener: OnceLock<ChangeListener>,
+    _guard: PathGuard,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct FrontMatter {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created_at: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    updated_at: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    modified_at: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    revision: Option<u64>,
+    // hand-written files are public unless they say otherwise or are scheduled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    visible: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    published_at: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    publish_at: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    summary: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    tags: BTreeSet<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    author: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    attachments: BTreeMap<String, Attachment>,
+    // present only while the article is in the trash
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    deleted_at: Option<DateTime<Local>>,
+}
+
+impl DirectoryStorage {
+    pub fn open(directory: impl AsRef<Path>) -> Result<Self> {
+        fs::create_dir_all(directory.as_ref()).context("create directory")?;
+        let guard = PathGuard::acquire(directory.as_ref())?;
+
+        Ok(Self {
+            directory: directory.as_ref().to_path_buf(),
+            cache: Mutex::new(HashMap::new()),
+            listener: OnceLock::new(),
+            _guard: guard,
+        })
+    }
+
+    fn path_of(&self, article_id: &ArticleId) -> Result<PathBuf> {
+        let id = article_id.to_string();
+        // the id becomes a file name, so it must not be able to point outside of the directory
+        if id.is_empty() || id.starts_with('.') || id.contains(['/', '\\']) {
+            bail!("{id:?} cannot be used as a file name");
+        }
+
+        Ok(self.directory.join(format!("{id}.{EXTENSION}")))
+    }
+
+    fn revisions_path_of(&self, article_id: &ArticleId) -> Result<PathBuf> {
+        // validates the id in the same way
+        self.path_of(article_id)?;
+        Ok(self.directory.join(REVISIONS_DIRECTORY).join(format!("{article_id}.json")))
+    }
+
+    fn read(&self, article_id: &ArticleId, path: &Path) -> Result<Option<Article>> {
+        let modified = match fs::metadata(path) {
+            Ok(metadata) => metadata.modified()?,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                self.cache.lock().unwrap().remove(article_id);
+                self.notify(article_id, None);
+                return Ok(None)
+            }
+            Err(e) => return Err(e).context("stat article"),
+        };
+
+        if let Some((cached_at, article)) = self.cache.lock().unwrap().get(article_id) {
+            if *cached_at == modified {
+                return Ok(Some(article.clone()))
+            }
+        }
+
+        info!("reading {path}", path = path.display());
+        let text = fs::read_to_string(path).context("read article")?;
+        let latest = self.revisions(article_id)?.pop();
+        let article = parse(article_id.clone(), &text, modified.into(), latest.as_ref().map(|revision| revision.content.as_str()))
+            .with_context(|| format!("parse {path}", path = path.display()))?;
+        self.cache.lock().unwrap().insert(article_id.clone(), (modified, article.clone()));
+        self.notify(article_id, Some(&article));
+        Ok(Some(article))
+    }
+
+    fn notify(&self, article_id: &ArticleId, article: Option<&Article>) {
+        if let Some(listener) = self.listener.get() {
+            listener(article_id, article);
+        }
+    }
+}
+
+// `latest_content` is the content of the latest revision, if any.
+fn parse(article_id: ArticleId, text: &str, modified: DateTime<Local>, latest_content: Option<&str>) -> Result<Article> {
+    let (front_matter, content) = match split_front_matter(text)? {
+        Some((header, content)) => (toml::from_str::<FrontMatter>(header).context("front matter")?, content),
+        // hand-written file without metadata
+        None => (FrontMatter::default(), text),
+    };
+
+    let created_at = front_matter.created_at.unwrap_or(modified);
+    // the storage's own writes change the modification time too, so it tells nothing about the content by itself
+    let edited_by_hand = latest_content.is_some_and(|latest_content| latest_content != content);
+    let updated_at = match front_matter.updated_at {
+        Some(updated_at) if edited_by_hand => updated_at.max(modified),
+        Some(updated_at) => updated_at,
+        None => modified,
+    };
+    // anything in the file may have been edited, though
+    let modified_at = front_matter.modified_at.map_or(modified, |modified_at| modified_at.max(modified)).max(updated_at);
+    let visible = front_matter.visible.unwrap_or_else(|| front_matter.publish_at.is_none());
+    let published_at = front_matter.published_at.or_else(|| visible.then_some(created_at));
+
+    Ok(Article {
+        created_at,
+        updated_at,
+        modified_at,
+        revision: front_matter.revision.unwrap_or(1),
+        content: content.to_string(),
+        id: article_id,
+        metadata: Metadata {
+            title: front_matter.title,
+            summary: front_matter.summary,
+            tags: front_matter.tags,
+            author: front_matter.author,
+        },
+        visible,
+        published_at,
+        publish_at: front_matter.publish_at,
+        attachments: front_matter.attachments,
+        deleted_at: front_matter.deleted_at,
+    })
+}
+
+fn render(article: &Article) -> Result<String> {
+    let front_matter = toml::to_string(&FrontMatter {
+        created_at: Some(article.created_at),
+        updated_at: Some(article.updated_at),
+        modified_at: Some(article.modified_at),
+        revision: Some(article.revision),
+        visible: Some(article.visible),
+        published_at: article.published_at,
+        publish_at: article.publish_at,
+        title: article.metadata.title.clone(),
+        summary: article.metadata.summary.clone(),
+        tags: article.metadata.tags.clone(),
+        author: article.metadata.author.clone(),
+        attachments: article.attachments.clone(),
+        deleted_at: article.deleted_at,
+    })?;
+
+    Ok(format!("{FRONT_MATTER_DELIMITER}\n{front_matter}{FRONT_MATTER_DELIMITER}\n{content}", content = article.content))
+}
+
+impl ArticleStorage for DirectoryStorage {
+    fn get(&self, article_id: &ArticleId) -> Result<Option<Article>> {
+        let Ok(path) = self.path_of(article_id) else {
+            return Ok(None)
+        };
+
+        self.read(article_id, &path)
+    }
+
+    fn put(&self, article: Article) -> Result<()> {
+        let path = self.path_of(&article.id)?;
+        write_atomically(&path, render(&article)?.as_bytes())?;
+        // cached as written, so that the new modification time is not taken for a hand edit
+        let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).context("stat article")?;
+        self.cache.lock().unwrap().insert(article.id.clone(), (modified, article));
+        Ok(())
+    }
+
+    fn remove(&self, article_id: &ArticleId) -> Result<()> {
+        remove_if_exists(&self.path_of(article_id)?).context("remove article")?;
+        self.cache.lock().unwrap().remove(article_id);
+        remove_if_exists(&self.revisions_path_of(article_id)?).context("remove revisions")?;
+        Ok(())
+    }
+
+    // Files that cannot be parsed are left out, so that a listing still shows the rest.
+    fn list(&self) -> Result<Vec<Article>> {
+        let mut articles = vec![];
+        let mut found = HashSet::new();
+        for entry in fs::read_dir(&self.directory).context("read directory")? {
+            let path = entry?.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some(EXTENSION) {
+                continue
+            }
+            let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue
+            };
+
+            let article_id = ArticleId::new(id.to_string());
+            // a file broken by hand must not take the others down with it
+            match self.read(&article_id, &path) {
+                Ok(Some(article)) => articles.push(article),
+                Ok(None) => {}
+                Err(e) => warn!("skipping {path}: {e:#}", path = path.display()),
+            }
+            found.insert(article_id);
+        }
+
+        // the files that have been read before but were deleted by hand since
+        let mut cache = self.cache.lock().unwrap();
+        let deleted: Vec<_> = cache.keys().filter(|article_id| !found.contains(*article_id)).cloned().collect();
+        for article_id in &deleted {
+            cache.remove(article_id);
+        }
+        drop(cache);
+        for article_id in &deleted {
+            self.notify(article_id, None);
+        }
+
+        Ok(articles)
+    }
+
+    fn exists(&self, article_id: &ArticleId) -> Result<bool> {
+        Ok(self.path_of(article_id).is_ok_and(|path| path.exists()))
+    }
+
+    fn revisions(&self, article_id: &ArticleId) -> Result<Vec<Revision>> {
+        let Ok(path) = self.revisions_path_of(article_id) else {
+            return Ok(vec![])
+        };
+
+        match fs::read(&path) {
+            Ok(json) => serde_json::from_slice(&json).with_context(|| format!("parse {path}", path = path.display())),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(vec![]),
+            Err(e) => Err(e).context("read revisions"),
+        }
+    }
+
+    // callers serialize writes, so reading and rewriting the whole history is fine
+    fn add_revision(&self, article_id: &ArticleId, revision: Revision) -> Result<()> {
+        let path = self.revisions_path_of(article_id)?;
+        let mut revisions = self.revisions(article_id)?;
+        revisions.push(revision);
+
+        fs::create_dir_all(self.directory.join(REVISIONS_DIRECTORY)).context("create revisions directory")?;
+        write_atomically(&path, &serde_json::to_vec(&revisions)?)
+    }
+
+    fn watch(&self, listener: ChangeListener) {
+        if self.listener.set(listener).is_err() {
+            warn!("a change listener is already registered; ignoring another one");
+        }
+    }
+}
+
+fn remove_if_exists(path: &Path) -> std::io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
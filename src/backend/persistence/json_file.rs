@@ -0,0 +1,448 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use anyhow::{bail, Context, Result};
+use log::{debug, error, info};
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use crate::backend::persistence::page::{page_of, ArticlePage, PageQuery};
+use crate::backend::persistence::{write_atomically, Article, ArticleId, ArticleStorage, PathGuard, Revision};
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum FlushPolicy {
+    // every mutation is written to the file before it is acknowledged
+    OnWrite,
+    // mutations only mark the storage dirty; `flush` must be called periodically
+    Deferred,
+}
+
+// Keeps every article in memory and persists all of them as a single JSON document.
+pub struct JsonFileStorage {
+    path: PathBuf,
+    data: RwLock<Entries>,
+    // Serializes writes to the file, so that it is written in the same order as the mutations were applied.
+    // `flush` acquires it while `data` is locked; a mutation under `OnWrite` holds it from copying `data` until replacing it.
+    file_lock: Mutex<()>,
+    dirty: AtomicBool,
+    flush_policy: FlushPolicy,
+    _guard: PathGuard,
+}
+
+#[derive(Default, Clone)]
+struct Entries {
+    articles: HashMap<ArticleId, Article>,
+    revisions: HashMap<ArticleId, Vec<Revision>>,
+}
+
+impl JsonFileStorage {
+    fn create_default_file_if_absent(path: impl AsRef<Path>) -> Result<()> {
+        if !path.as_ref().exists() {
+            write_atomically(path.as_ref(), &to_document(&Entries::default())?)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn open(path: impl AsRef<Path>, flush_policy: FlushPolicy) -> Result<Self> {
+        Self::create_default_file_if_absent(path.as_ref())?;
+        let guard = PathGuard::acquire(path.as_ref())?;
+        let (data, upgraded) = Self::read_file(path.as_ref())?;
+        if upgraded {
+            // persist the upgraded document so that migrations run only once
+            write_atomically(path.as_ref(), &to_document(&data)?)?;
+        }
+
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            data: RwLock::new(data),
+            file_lock: Mutex::new(()),
+            dirty: AtomicBool::new(false),
+            flush_policy,
+            _guard: guard,
+        })
+    }
+
+    // Reads the articles of a file along with their revisions without opening it as a storage,
+    // e.g. to import them into another one.
+    pub fn read_articles(path: impl AsRef<Path>) -> Result<Vec<(Article, Vec<Revision>)>> {
+        let Entries { articles, mut revisions } = Self::read_file(path.as_ref())?.0;
+        Ok(articles.into_values().map(|article| {
+            let history = revisions.remove(&article.id).unwrap_or_default();
+            (article, history)
+        }).collect())
+    }
+
+    fn modify(&self, f: impl FnOnce(&mut Entries)) -> Result<()> {
+        if self.flush_policy == FlushPolicy::Deferred {
+            f(&mut self.data.write().unwrap());
+            info!("modified");
+            self.dirty.store(true, Ordering::Release);
+            return Ok(())
+        }
+
+        // The mutation is applied to a copy, which replaces the entries only once it has been written,
+        // so a failed write leaves nothing behind. Readers see the old entries while the file is being replaced.
+        let _file_lock = self.file_lock.lock().unwrap();
+        let mut modified = self.data.read().unwrap().clone();
+        f(&mut modified);
+        info!("modified");
+        write_atomically(&self.path, &to_document(&modified)?)?;
+        info!("wrote");
+        *self.data.write().unwrap() = modified;
+        Ok(())
+    }
+
+    // must be called while `data` is locked
+    fn prepare_write(&self, data: &Entries) -> Result<PendingWrite<'_>> {
+        let file_lock = self.file_lock.lock().unwrap();
+        let json = to_document(data)?;
+        self.dirty.store(false, Ordering::Release);
+
+        Ok(PendingWrite {
+            _file_lock: file_lock,
+            json,
+        })
+    }
+
+    fn complete_write(&self, PendingWrite { _file_lock, json }: PendingWrite<'_>) -> Result<()> {
+        write_atomically(&self.path, &json).inspect_err(|_| {
+            // let the next flush retry
+            self.dirty.store(true, Ordering::Release);
+        })?;
+        info!("wrote");
+        Ok(())
+    }
+
+    // Also returns whether the document was written in an older version.
+    fn read_file(path: &Path) -> Result<(Entries, bool)> {
+        let file = File::options().read(true).open(path).context("open file")?;
+        let mut read_all = BufReader::new(file);
+        let mut buf = vec![];
+        read_all.read_to_end(&mut buf).context("verify file")?;
+        let got = String::from_utf8(buf).context("utf8 verify")?;
+        debug!("file JSON: {got}", got = &got);
+
+        let document: Value = serde_json::from_str(got.as_str()).inspect_err(|e| {
+            error!("{e}", e = &e);
+        }).context("reading json file")?;
+        let (document, upgraded) = upgrade(document)?;
+        let document: FileScheme = serde_json::from_value(document).context("reading json file")?;
+
+        let entries = Entries {
+            articles: document.articles.into_iter().map(|article| (article.id.clone(), article)).collect(),
+            revisions: document.revisions.into_iter().collect(),
+        };
+        Ok((entries, upgraded))
+    }
+}
+
+impl ArticleStorage for JsonFileStorage {
+    fn get(&self, article_id: &ArticleId) -> Result<Option<Article>> {
+        Ok(self.data.read().unwrap().articles.get(article_id).cloned())
+    }
+
+    fn put(&self, article: Article) -> Result<()> {
+        self.modify(|a| {
+            a.articles.insert(article.id.clone(), article);
+        })
+    }
+
+    fn remove(&self, article_id: &ArticleId) -> Result<()> {
+        self.modify(|a| {
+            a.articles.remove(article_id);
+            a.revisions.remove(article_id);
+        })
+    }
+
+    fn list(&self) -> Result<Vec<Article>> {
+        Ok(self.data.read().unwrap().articles.values().cloned().collect())
+    }
+
+    // only the articles on the page are cloned
+    fn list_page(&self, query: &PageQuery) -> Result<ArticlePage> {
+        Ok(page_of(self.data.read().unwrap().articles.values(), query))
+    }
+
+    fn exists(&self, article_id: &ArticleId) -> Result<bool> {
+        Ok(self.data.read().unwrap().articles.contains_key(article_id))
+    }
+
+    fn revisions(&self, article_id: &ArticleId) -> Result<Vec<Revision>> {
+        Ok(self.data.read().unwrap().revisions.get(article_id).cloned().unwrap_or_default())
+    }
+
+    fn add_revision(&self, article_id: &ArticleId, revision: Revision) -> Result<()> {
+        self.modify(|a| {
+            a.revisions.entry(article_id.clone()).or_default().push(revision);
+        })
+    }
+
+    // a single write of the file
+    fn put_with_revisions(&self, article: Article, revisions: Vec<Revision>) -> Result<()> {
+        self.modify(|a| {
+            a.revisions.entry(article.id.clone()).or_default().extend(revisions);
+            a.articles.insert(article.id.clone(), article);
+        })
+    }
+
+    // Writes the entries to the file if there are unsaved mutations.
+    fn flush(&self) -> Result<()> {
+        let a = self.data.read().unwrap();
+        if !self.dirty.load(Ordering::Acquire) {
+            return Ok(())
+        }
+
+        let pending = self.prepare_write(&a)?;
+        drop(a);
+        self.complete_write(pending)
+    }
+}
+
+// holds the file lock from serialization until the file is replaced
+struct PendingWrite<'a> {
+    _file_lock: MutexGuard<'a, ()>,
+    json: Vec<u8>,
+}
+
+const CURRENT_VERSION: u64 = 9;
+
+// The persisted document. Whenever its shape changes, bump CURRENT_VERSION and append a migration.
+#[derive(Serialize, Deserialize)]
+struct FileScheme<A = Article, R = Vec<Revision>> {
+    version: u64,
+    articles: Vec<A>,
+    revisions: BTreeMap<ArticleId, R>,
+}
+
+fn to_document(entries: &Entries) -> Result<Vec<u8>> {
+    let mut articles: Vec<_> = entries.articles.values().collect();
+    // keeps the file diffable
+    articles.sort_unstable_by(|a, b| a.id.cmp(&b.id));
+
+    Ok(serde_json::to_vec(&FileScheme {
+        version: CURRENT_VERSION,
+        articles,
+        revisions: entries.revisions.iter().map(|(article_id, revisions)| (article_id.clone(), revisions)).collect(),
+    })?)
+}
+
+// MIGRATIONS[n] upgrades a document from version n + 1 to n + 2.
+const MIGRATIONS: &[fn(Value) -> Result<Value>] = &[
+    migrate_v1_to_v2,
+    migrate_v2_to_v3,
+    migrate_v3_to_v4,
+    migrate_v4_to_v5,
+    migrate_v5_to_v6,
+    migrate_v6_to_v7,
+    migrate_v7_to_v8,
+    migrate_v8_to_v9,
+];
+
+// Applies every migration the document needs. Also returns whether any was applied.
+fn upgrade(mut document: Value) -> Result<(Value, bool)> {
+    let version = match document.get("version") {
+        // the format before versioning was introduced
+        None => 1,
+        Some(version) => version.as_u64().context("`version` must be an unsigned integer")?,
+    };
+
+    if version == 0 || version > CURRENT_VERSION {
+        bail!("unsupported data file version {version}; this program supports up to {CURRENT_VERSION}");
+    }
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(usize::try_from(version)? - 1) {
+        info!("migrating data file from version {from} to {to}", from = index + 1, to = index + 2);
+        document = migration(document)?;
+    }
+
+    Ok((document, version < CURRENT_VERSION))
+}
+
+// Version 1 is `{ "data": { "<id>": <article> } }`, where articles may lack `updated_at` and `revision`.
+fn migrate_v1_to_v2(mut document: Value) -> Result<Value> {
+    let data = document.get_mut("data").and_then(Value::as_object_mut).context("`data` must be an object")?;
+
+    let articles = std::mem::take(data).into_iter().map(|(_, mut article)| {
+        let article_object = article.as_object_mut().context("article must be an object")?;
+        let created_at = article_object.get("created_at").cloned().context("article must have `created_at`")?;
+        // treated as never having been updated since creation
+        article_object.entry("updated_at").or_insert(created_at);
+        article_object.entry("revision").or_insert_with(|| json!(1));
+        Ok(article)
+    }).collect::<Result<Vec<_>>>()?;
+
+    Ok(json!({
+        "version": 2,
+        "articles": articles,
+    }))
+}
+
+// Version 3 adds the revision history, which starts out empty.
+fn migrate_v2_to_v3(mut document: Value) -> Result<Value> {
+    let document_object = document.as_object_mut().context("document must be an object")?;
+    document_object.insert("version".to_string(), json!(3));
+    document_object.insert("revisions".to_string(), json!({}));
+
+    Ok(document)
+}
+
+// Version 4 adds `deleted_at` to articles; none of them is in the trash yet.
+fn migrate_v3_to_v4(mut document: Value) -> Result<Value> {
+    let articles = document.get_mut("articles").and_then(Value::as_array_mut).context("`articles` must be an array")?;
+    for article in articles {
+        article.as_object_mut().context("article must be an object")?.insert("deleted_at".to_string(), Value::Null);
+    }
+    document.as_object_mut().context("document must be an object")?.insert("version".to_string(), json!(4));
+
+    Ok(document)
+}
+
+// Version 5 adds `visible` and `published_at` to articles. Every article was public until then.
+fn migrate_v4_to_v5(mut document: Value) -> Result<Value> {
+    let articles = document.get_mut("articles").and_then(Value::as_array_mut).context("`articles` must be an array")?;
+    for article in articles {
+        let article_object = article.as_object_mut().context("article must be an object")?;
+        let created_at = article_object.get("created_at").cloned().context("article must have `created_at`")?;
+        article_object.insert("visible".to_string(), json!(true));
+        article_object.insert("published_at".to_string(), created_at);
+    }
+    document.as_object_mut().context("document must be an object")?.insert("version".to_string(), json!(5));
+
+    Ok(document)
+}
+
+// Version 6 adds `publish_at` to articles; nothing is scheduled yet.
+fn migrate_v5_to_v6(mut document: Value) -> Result<Value> {
+    let articles = document.get_mut("articles").and_then(Value::as_array_mut).context("`articles` must be an array")?;
+    for article in articles {
+        article.as_object_mut().context("article must be an object")?.insert("publish_at".to_string(), Value::Null);
+    }
+    document.as_object_mut().context("document must be an object")?.insert("version".to_string(), json!(6));
+
+    Ok(document)
+}
+
+// Version 7 adds `attachments` to articles.
+fn migrate_v6_to_v7(mut document: Value) -> Result<Value> {
+    let articles = document.get_mut("articles").and_then(Value::as_array_mut).context("`articles` must be an array")?;
+    for article in articles {
+        article.as_object_mut().context("article must be an object")?.insert("attachments".to_string(), json!({}));
+    }
+    document.as_object_mut().context("document must be an object")?.insert("version".to_string(), json!(7));
+
+    Ok(document)
+}
+
+// Version 8 adds `metadata` to articles.
+fn migrate_v7_to_v8(mut document: Value) -> Result<Value> {
+    let articles = document.get_mut("articles").and_then(Value::as_array_mut).context("`articles` must be an array")?;
+    for article in articles {
+        article.as_object_mut().context("article must be an object")?.insert("metadata".to_string(), json!({}));
+    }
+    document.as_object_mut().context("document must be an object")?.insert("version".to_string(), json!(8));
+
+    Ok(document)
+}
+
+// Version 9 adds `modified_at` to articles; nothing is known to have changed after the content.
+fn migrate_v8_to_v9(mut document: Value) -> Result<Value> {
+    let articles = document.get_mut("articles").and_then(Value::as_array_mut).context("`articles` must be an array")?;
+    for article in articles {
+        let article_object = article.as_object_mut().context("article must be an object")?;
+        let updated_at = article_object.get("updated_at").cloned().context("article must have `updated_at`")?;
+        article_object.insert("modified_at".to_string(), updated_at);
+    }
+    document.as_object_mut().context("document must be an object")?.insert("version".to_string(), json!(9));
+
+    Ok(document)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use chrono::{DateTime, Local};
+    use serde_json::json;
+    use crate::backend::persistence::{ArticleId, ArticleStorage, Metadata};
+    use crate::backend::persistence::test_support::{published_article, temp_dir};
+    use super::{to_document, upgrade, FlushPolicy, JsonFileStorage, CURRENT_VERSION};
+
+    const CREATED_AT: &str = "2022-06-20T12:34:56.789+09:00";
+    const UPDATED_AT: &str = "2022-07-01T00:00:00+09:00";
+
+    #[test]
+    fn upgrades_v1_document() {
+        let path = temp_dir("upgrades_v1_document").join("index.json");
+        fs::write(&path, json!({
+            "data": {
+                "old": { "created_at": CREATED_AT, "content": "old", "id": "old" },
+                "edited": { "created_at": CREATED_AT, "updated_at": UPDATED_AT, "revision": 3, "content": "edited", "id": "edited" },
+            }
+        }).to_string()).unwrap();
+
+        let (entries, upgraded) = JsonFileStorage::read_file(&path).unwrap();
+        assert!(upgraded);
+        assert!(entries.revisions.is_empty());
+
+        let old = &entries.articles[&ArticleId::new("old".to_string())];
+        let created_at = DateTime::parse_from_rfc3339(CREATED_AT).unwrap();
+        assert_eq!(old.created_at, created_at);
+        assert_eq!(old.updated_at, created_at);
+        assert_eq!(old.modified_at, created_at);
+        assert_eq!(old.revision, 1);
+        assert_eq!(old.content, "old");
+        // every article was public before drafts were introduced
+        assert!(old.visible);
+        assert_eq!(old.published_at, Some(created_at.into()));
+        assert_eq!(old.publish_at, None);
+        assert_eq!(old.deleted_at, None);
+        assert!(old.attachments.is_empty());
+        assert_eq!(old.metadata, Metadata::default());
+
+        let edited = &entries.articles[&ArticleId::new("edited".to_string())];
+        assert_eq!(edited.created_at, created_at);
+        assert_eq!(edited.updated_at, DateTime::parse_from_rfc3339(UPDATED_AT).unwrap());
+        assert_eq!(edited.revision, 3);
+    }
+
+    #[test]
+    fn current_document_is_not_upgraded() {
+        let path = temp_dir("current_document_is_not_upgraded").join("index.json");
+        let (entries, _) = {
+            fs::write(&path, json!({ "data": { "a": { "created_at": CREATED_AT, "content": "a", "id": "a" } } }).to_string()).unwrap();
+            JsonFileStorage::read_file(&path).unwrap()
+        };
+        fs::write(&path, to_document(&entries).unwrap()).unwrap();
+
+        let (reread, upgraded) = JsonFileStorage::read_file(&path).unwrap();
+        assert!(!upgraded);
+        assert_eq!(reread.articles.len(), 1);
+    }
+
+    #[test]
+    fn failed_write_is_not_applied() {
+        let directory = temp_dir("failed_write_is_not_applied");
+        let path = directory.join("index.json");
+        let storage = JsonFileStorage::open(&path, FlushPolicy::OnWrite).unwrap();
+
+        // the temporary file cannot be created over a directory
+        fs::create_dir(directory.join("index.json.tmp")).unwrap();
+        assert!(storage.put(published_article("failed", Local::now())).is_err());
+        assert!(storage.get(&ArticleId::new("failed".to_string())).unwrap().is_none());
+
+        // nor is it persisted by the next write
+        fs::remove_dir(directory.join("index.json.tmp")).unwrap();
+        storage.put(published_article("next", Local::now())).unwrap();
+        let (entries, _) = JsonFileStorage::read_file(&path).unwrap();
+        assert_eq!(entries.articles.keys().map(ToString::to_string).collect::<Vec<_>>(), ["next"]);
+    }
+
+    #[test]
+    fn rejects_unknown_versions() {
+        assert!(upgrade(json!({ "version": 0, "articles": [], "revisions": {} })).is_err());
+        assert!(upgrade(json!({ "version": CURRENT_VERSION + 1, "articles": [], "revisions": {} })).is_err());
+        assert!(upgrade(json!({ "version": "7", "articles": [], "revisions": {} })).is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use std::cmp::Ordering;
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+use crate::backend::persistence::{Article, ArticleId};
+
+// One page of the live articles, as `ArticleStorage::list_page` should return it.
+pub struct PageQuery {
+    pub sort: SortKey,
+    pub order: SortOrder,
+    // inclusive bound on the sort key
+    pub since: Option<DateTime<Local>>,
+    // exclusive bound on the sort key
+    pub until: Option<DateTime<Local>>,
+    // the sort key and id of the last article of the previous page
+    pub after: Option<(DateTime<Local>, ArticleId)>,
+    pub offset: usize,
+    pub limit: usize,
+    // if set, drafts are left out unless they are scheduled to be visible by then
+    pub visible_at: Option<DateTime<Local>>,
+}
+
+#[derive(Deserialize, Eq, PartialEq, Copy, Clone, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    #[default]
+    Created,
+    Updated,
+}
+
+impl SortKey {
+    pub const fn of(self, article: &Article) -> DateTime<Local> {
+        match self {
+            Self::Created => article.created_at,
+            Self::Updated => article.updated_at,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::Updated => "updated",
+        }
+    }
+}
+
+#[derive(Deserialize, Eq, PartialEq, Copy, Clone, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+// Listings do not show the bodies, so `content` of the articles is left empty.
+pub struct ArticlePage {
+    pub articles: Vec<Article>,
+    // number of articles that matched the filters, across all pages
+    pub total: usize,
+    // whether there are articles after this page
+    pub has_more: bool,
+}
+
+impl PageQuery {
+    fn matches(&self, article: &Article) -> bool {
+        let key = self.sort.of(article);
+        article.deleted_at.is_none()
+            && self.visible_at.is_none_or(|now| article.visible || article.publish_at.is_some_and(|publish_at| publish_at <= now))
+            && self.since.is_none_or(|since| key >= since)
+            && self.until.is_none_or(|until| key < until)
+    }
+
+    // ties are broken by id, so that pages never overlap nor skip an article
+    fn compare(&self, a: (DateTime<Local>, &ArticleId), b: (DateTime<Local>, &ArticleId)) -> Ordering {
+        let ordering = a.cmp(&b);
+        match self.order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    }
+}
+
+// For the storages that keep their articles in memory; only the articles on the page are cloned.
+pub fn page_of<'a>(articles: impl IntoIterator<Item = &'a Article>, query: &PageQuery) -> ArticlePage {
+    let key = query.sort;
+    let mut matched: Vec<_> = articles.into_iter().filter(|article| query.matches(article)).collect();
+    let total = matched.len();
+    matched.sort_unstable_by(|a, b| query.compare((key.of(a), &a.id), (key.of(b), &b.id)));
+
+    let start = query.after.as_ref().map_or(query.offset, |(timestamp, id)| {
+        matched.partition_point(|article| query.compare((key.of(article), &article.id), (*timestamp, id)) != Ordering::Greater)
+    });
+    let mut articles: Vec<_> = matched.into_iter()
+        .skip(start)
+        .take(query.limit + 1)
+        .map(|article| Article {
+            content: String::new(),
+            ..article.clone()
+        })
+        .collect();
+    let has_more = articles.len() > query.limit;
+    articles.truncate(query.limit);
+
+    ArticlePage {
+        articles,
+        total,
+        has_more,
+    }
+}
@@ -0,0 +1,446 @@
+use std::path::Path;
+use std::sync::Mutex;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use log::info;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row, Transaction};
+use serde::de::DeserializeOwned;
+use crate::backend::persistence::page::{ArticlePage, PageQuery, SortKey, SortOrder};
+use crate::backend::persistence::{Article, ArticleId, ArticleStorage, PathGuard, Revision};
+
+// MIGRATIONS[n] upgrades a database from schema version n to n + 1.
+// The current version is kept in `PRAGMA user_version`; never edit a migration that has been released.
+const MIGRATIONS: &[&str] = &[
+    // 0 -> 1
+    "
+    CREATE TABLE articles (
+        id TEXT PRIMARY KEY NOT NULL,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL,
+        revision INTEGER NOT NULL,
+        content TEXT NOT NULL
+    );
+    CREATE INDEX articles_created_at ON articles (created_at);
+    CREATE INDEX articles_updated_at ON articles (updated_at);
+    ",
+    // 1 -> 2
+    "
+    CREATE TABLE article_revisions (
+        article_id TEXT NOT NULL,
+        number INTEGER NOT NULL,
+        created_at TEXT NOT NULL,
+        token_id TEXT,
+        content TEXT NOT NULL,
+        PRIMARY KEY (article_id, number)
+    );
+    ",
+    // 2 -> 3
+    "
+    ALTER TABLE articles ADD COLUMN deleted_at TEXT;
+    ",
+    // 3 -> 4; every article was public until then
+    "
+    ALTER TABLE articles ADD COLUMN visible INTEGER NOT NULL DEFAULT 1;
+    ALTER TABLE articles ADD COLUMN published_at TEXT;
+    UPDATE articles SET published_at = created_at;
+    ",
+    // 4 -> 5
+    "
+    ALTER TABLE articles ADD COLUMN publish_at TEXT;
+    ",
+    // 5 -> 6; attachment metadata is small and always read along with the article, so it is kept as a JSON object
+    "
+    ALTER TABLE articles ADD COLUMN attachments TEXT NOT NULL DEFAULT '{}';
+    ",
+    // 6 -> 7; likewise a JSON object
+    "
+    ALTER TABLE articles ADD COLUMN metadata TEXT NOT NULL DEFAULT '{}';
+    ",
+    // 7 -> 8; listings break ties on the timestamps by id, so the indexes cover both to spare sorting
+    "
+    DROP INDEX articles_created_at;
+    DROP INDEX articles_updated_at;
+    CREATE INDEX articles_created_at_id ON articles (created_at, id);
+    CREATE INDEX articles_updated_at_id ON articles (updated_at, id);
+    ",
+    // 8 -> 9; nothing is known to have changed after the content
+    "
+    ALTER TABLE articles ADD COLUMN modified_at TEXT NOT NULL DEFAULT '';
+    UPDATE articles SET modified_at = updated_at;
+    ",
+];
+
+const ARTICLE_COLUMNS: &str = "id, created_at, updated_at, revision, content, deleted_at, visible, published_at, publish_at, attachments, metadata, modified_at";
+// the same columns, except that listings leave out the bodies
+const SUMMARY_COLUMNS: &str = "id, created_at, updated_at, revision, '', deleted_at, visible, published_at, publish_at, attachments, metadata, modified_at";
+
+pub struct SqliteStorage {
+    connection: Mutex<Connection>,
+    _guard: PathGuard,
+}
+
+impl SqliteStorage {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let mut connection = Connection::open(path.as_ref()).context("open database")?;
+        let guard = PathGuard::acquire(path.as_ref())?;
+        Self::migrate(&mut connection)?;
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+            _guard: guard,
+        })
+    }
+
+    fn migrate(connection: &mut Connection) -> Result<()> {
+        let transaction = connection.transaction()?;
+        let version: usize = transaction.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+        for (from, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            info!("migrating database schema from version {from} to {to}", to = from + 1);
+            transaction.execute_batch(migration).with_context(|| format!("migration from version {from}"))?;
+        }
+
+        if version < MIGRATIONS.len() {
+            transaction.pragma_update(None, "user_version", MIGRATIONS.len())?;
+        }
+
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn with_connection<T>(&self, f: impl FnOnce(&mut Connection) -> Result<T>) -> Result<T> {
+        f(&mut self.connection.lock().unwrap())
+    }
+
+    fn insert(transaction: &Transaction<'_>, article: &Article) -> Result<()> {
+        transaction.execute(
+            "INSERT OR REPLACE INTO articles (id, created_at, updated_at, revision, content, deleted_at, visible, published_at, publish_at, attachments, metadata, modified_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                article.id.to_string(),
+                fmt_timestamp(&article.created_at),
+                fmt_timestamp(&article.updated_at),
+                article.revision,
+                article.content,
+                article.deleted_at.as_ref().map(fmt_timestamp),
+                article.visible,
+                article.published_at.as_ref().map(fmt_timestamp),
+                article.publish_at.as_ref().map(fmt_timestamp),
+                serde_json::to_string(&article.attachments)?,
+                serde_json::to_string(&article.metadata)?,
+                fmt_timestamp(&article.modified_at),
+            ],
+        )?;
+        Ok(())
+    }
+
+    // also takes a transaction, which dereferences to its connection
+    fn insert_revision(connection: &Connection, article_id: &ArticleId, revision: &Revision) -> Result<()> {
+        connection.execute(
+            "INSERT OR REPLACE INTO article_revisions (article_id, number, created_at, token_id, content) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                article_id.to_string(),
+                revision.number,
+                fmt_timestamp(&revision.created_at),
+                revision.token_id,
+                revision.content,
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+// Timestamps are stored as UTC with a fixed number of fractional digits,
+// so that the lexicographic order of the indexed columns matches the chronological one.
+fn fmt_timestamp(timestamp: &DateTime<Local>) -> String {
+    timestamp.with_timezone(&Utc).to_rfc3339_opts(SecondsFormat::Nanos, true)
+}
+
+fn parse_timestamp(row: &Row<'_>, index: usize) -> rusqlite::Result<DateTime<Local>> {
+    let text: String = row.get(index)?;
+    parse_timestamp_text(index, &text)
+}
+
+fn parse_optional_timestamp(row: &Row<'_>, index: usize) -> rusqlite::Result<Option<DateTime<Local>>> {
+    let text: Option<String> = row.get(index)?;
+    text.map(|text| parse_timestamp_text(index, &text)).transpose()
+}
+
+fn parse_timestamp_text(index: usize, text: &str) -> rusqlite::Result<DateTime<Local>> {
+    DateTime::parse_from_rfc3339(text)
+        .map(|timestamp| timestamp.with_timezone(&Local))
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(e)))
+}
+
+fn article_from_row(row: &Row<'_>) -> rusqlite::Result<Article> {
+    Ok(Article {
+        id: ArticleId::new(row.get(0)?),
+        created_at: parse_timestamp(row, 1)?,
+        updated_at: parse_timestamp(row, 2)?,
+        revision: row.get(3)?,
+        content: row.get(4)?,
+        deleted_at: parse_optional_timestamp(row, 5)?,
+        visible: row.get(6)?,
+        published_at: parse_optional_timestamp(row, 7)?,
+        publish_at: parse_optional_timestamp(row, 8)?,
+        attachments: parse_json(row, 9)?,
+        metadata: parse_json(row, 10)?,
+        modified_at: parse_timestamp(row, 11)?,
+    })
+}
+
+fn parse_json<T: DeserializeOwned>(row: &Row<'_>, index: usize) -> rusqlite::Result<T> {
+    let json: String = row.get(index)?;
+    serde_json::from_str(&json).map_err(|e| rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(e)))
+}
+
+struct Sql {
+    sql: String,
+    params: Vec<String>,
+}
+
+// The statements that count the articles matching the query and select its page, in this order.
+// The page is read in the order of the `(created_at, id)` or `(updated_at, id)` index, so that it takes no sorting.
+fn page_statements(query: &PageQuery) -> (Sql, Sql) {
+    let column = match query.sort {
+        SortKey::Created => "created_at",
+        SortKey::Updated => "updated_at",
+    };
+    let (direction, after) = match query.order {
+        SortOrder::Asc => ("ASC", ">"),
+        SortOrder::Desc => ("DESC", "<"),
+    };
+
+    // timestamps are compared as text, which works as they are all formatted by `fmt_timestamp`
+    let mut conditions = vec!["deleted_at IS NULL".to_string()];
+    let mut params = vec![];
+    if let Some(now) = &query.visible_at {
+        conditions.push("(visible OR publish_at <= ?)".to_string());
+        params.push(fmt_timestamp(now));
+    }
+    if let Some(since) = &query.since {
+        conditions.push(format!("{column} >= ?"));
+        params.push(fmt_timestamp(since));
+    }
+    if let Some(until) = &query.until {
+        conditions.push(format!("{column} < ?"));
+        params.push(fmt_timestamp(until));
+    }
+
+    let count = Sql {
+        sql: format!("SELECT COUNT(*) FROM articles WHERE {filter}", filter = conditions.join(" AND ")),
+        params: params.clone(),
+    };
+
+    if let Some((timestamp, id)) = &query.after {
+        conditions.push(format!("({column}, id) {after} (?, ?)"));
+        params.push(fmt_timestamp(timestamp));
+        params.push(id.to_string());
+    }
+    let select = Sql {
+        // one more than the limit tells whether there is a next page
+        sql: format!(
+            "SELECT {SUMMARY_COLUMNS} FROM articles WHERE {filter} ORDER BY {column} {direction}, id {direction} LIMIT {limit} OFFSET {offset}",
+            filter = conditions.join(" AND "),
+            limit = query.limit + 1,
+            offset = query.offset,
+        ),
+        params,
+    };
+
+    (count, select)
+}
+
+fn revision_from_row(row: &Row<'_>) -> rusqlite::Result<Revision> {
+    Ok(Revision {
+        number: row.get(0)?,
+        created_at: parse_timestamp(row, 1)?,
+        token_id: row.get(2)?,
+        content: row.get(3)?,
+    })
+}
+
+impl ArticleStorage for SqliteStorage {
+    fn get(&self, article_id: &ArticleId) -> Result<Option<Article>> {
+        self.with_connection(|connection| {
+            connection.query_row(
+                &format!("SELECT {ARTICLE_COLUMNS} FROM articles WHERE id = ?1"),
+                [article_id.to_string()],
+                article_from_row,
+            ).optional().context("select article")
+        })
+    }
+
+    fn put(&self, article: Article) -> Result<()> {
+        self.with_connection(|connection| {
+            let transaction = connection.transaction()?;
+            Self::insert(&transaction, &article)?;
+            transaction.commit()?;
+            Ok(())
+        })
+    }
+
+    fn remove(&self, article_id: &ArticleId) -> Result<()> {
+        self.with_connection(|connection| {
+            let transaction = connection.transaction()?;
+            transaction.execute("DELETE FROM articles WHERE id = ?1", [article_id.to_string()])?;
+            transaction.execute("DELETE FROM article_revisions WHERE article_id = ?1", [article_id.to_string()])?;
+            transaction.commit()?;
+            Ok(())
+        })
+    }
+
+    fn list(&self) -> Result<Vec<Article>> {
+        self.with_connection(|connection| {
+            let mut statement = connection.prepare(&format!("SELECT {ARTICLE_COLUMNS} FROM articles"))?;
+            let articles = statement.query_map([], article_from_row)?.collect::<rusqlite::Result<_>>()?;
+            Ok(articles)
+        })
+    }
+
+    fn list_page(&self, query: &PageQuery) -> Result<ArticlePage> {
+        let (count, select) = page_statements(query);
+        self.with_connection(|connection| {
+            let total: usize = connection.query_row(&count.sql, params_from_iter(&count.params), |row| row.get(0))?;
+            let mut statement = connection.prepare(&select.sql)?;
+            let mut articles: Vec<_> = statement.query_map(params_from_iter(&select.params), article_from_row)?.collect::<rusqlite::Result<_>>()?;
+            let has_more = articles.len() > query.limit;
+            articles.truncate(query.limit);
+
+            Ok(ArticlePage {
+                articles,
+                total,
+                has_more,
+            })
+        })
+    }
+
+    fn exists(&self, article_id: &ArticleId) -> Result<bool> {
+        self.with_connection(|connection| {
+            let exists = connection.query_row(
+                "SELECT EXISTS (SELECT 1 FROM articles WHERE id = ?1)",
+                [article_id.to_string()],
+                |row| row.get(0),
+            )?;
+            Ok(exists)
+        })
+    }
+
+    fn revisions(&self, article_id: &ArticleId) -> Result<Vec<Revision>> {
+        self.with_connection(|connection| {
+            let mut statement = connection.prepare(
+                "SELECT number, created_at, token_id, content FROM article_revisions WHERE article_id = ?1 ORDER BY number",
+            )?;
+            let revisions = statement.query_map([article_id.to_string()], revision_from_row)?.collect::<rusqlite::Result<_>>()?;
+            Ok(revisions)
+        })
+    }
+
+    fn add_revision(&self, article_id: &ArticleId, revision: Revision) -> Result<()> {
+        self.with_connection(|connection| Self::insert_revision(connection, article_id, &revision))
+    }
+
+    fn put_with_revisions(&self, article: Article, revisions: Vec<Revision>) -> Result<()> {
+        self.with_connection(|connection| {
+            let transaction = connection.transaction()?;
+            for revision in &revisions {
+                Self::insert_revision(&transaction, &article.id, revision)?;
+            }
+            Self::insert(&transaction, &article)?;
+            transaction.commit()?;
+            Ok(())
+        })
+    }
+
+    fn import(&self, articles: Vec<(Article, Vec<Revision>)>) -> Result<()> {
+        self.with_connection(|connection| {
+            let transaction = connection.transaction()?;
+            for (article, revisions) in &articles {
+                for revision in revisions {
+                    Self::insert_revision(&transaction, &article.id, revision)?;
+                }
+                Self::insert(&transaction, article)?;
+            }
+            transaction.commit()?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Local;
+    use crate::backend::persistence::page::{page_of, ArticlePage, PageQuery, SortKey, SortOrder};
+    use crate::backend::persistence::{Article, ArticleId, ArticleStorage, Metadata};
+    use crate::backend::persistence::test_support::temp_dir;
+    use super::{page_statements, SqliteStorage};
+
+    fn query(sort: SortKey, order: SortOrder, after: Option<&Article>) -> PageQuery {
+        PageQuery {
+            sort,
+            order,
+            since: None,
+            until: None,
+            after: after.map(|article| (sort.of(article), article.id.clone())),
+            offset: 0,
+            limit: 2,
+            visible_at: Some(Local::now()),
+        }
+    }
+
+    fn ids(page: &ArticlePage) -> Vec<String> {
+        page.articles.iter().map(|article| article.id.to_string()).collect()
+    }
+
+    #[test]
+    fn listing_reads_index_without_sorting() {
+        let storage = SqliteStorage::open(temp_dir("listing_reads_index_without_sorting").join("index.sqlite3")).unwrap();
+        let article = Article::new(ArticleId::new("a".to_string()), String::new(), Metadata::default());
+
+        for (sort, index) in [(SortKey::Created, "articles_created_at_id"), (SortKey::Updated, "articles_updated_at_id")] {
+            for order in [SortOrder::Asc, SortOrder::Desc] {
+                for after in [None, Some(&article)] {
+                    let (_, select) = page_statements(&query(sort, order, after));
+                    let plan: Vec<String> = storage.with_connection(|connection| {
+                        let mut statement = connection.prepare(&format!("EXPLAIN QUERY PLAN {sql}", sql = select.sql))?;
+                        let plan = statement.query_map(rusqlite::params_from_iter(&select.params), |row| row.get(3))?.collect::<rusqlite::Result<_>>()?;
+                        Ok(plan)
+                    }).unwrap();
+
+                    assert!(plan.iter().any(|step| step.contains(index)), "{plan:?}");
+                    assert!(!plan.iter().any(|step| step.contains("TEMP B-TREE")), "{plan:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn list_page_agrees_with_in_memory_paging_over_ties() {
+        let storage = SqliteStorage::open(temp_dir("list_page_agrees_with_in_memory_paging_over_ties").join("index.sqlite3")).unwrap();
+        let created_at = Local::now();
+        let articles: Vec<_> = ["c", "a", "e", "b", "d"].into_iter().map(|id| Article {
+            created_at,
+            visible: true,
+            ..Article::new(ArticleId::new(id.to_string()), String::new(), Metadata::default())
+        }).collect();
+        storage.import(articles.iter().map(|article| (article.clone(), vec![])).collect()).unwrap();
+
+        for (order, expected) in [(SortOrder::Asc, ["a", "b", "c", "d", "e"]), (SortOrder::Desc, ["e", "d", "c", "b", "a"])] {
+            let mut seen = vec![];
+            let mut last = None;
+            loop {
+                let query = query(SortKey::Created, order, last.as_ref());
+                let page = storage.list_page(&query).unwrap();
+                assert_eq!(ids(&page), ids(&page_of(&articles, &query)));
+                assert_eq!(page.total, 5);
+
+                seen.extend(ids(&page));
+                last = page.articles.last().cloned();
+                if !page.has_more {
+                    break
+                }
+            }
+
+            assert_eq!(seen, expected);
+        }
+    }
+}
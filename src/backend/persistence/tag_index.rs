@@ -0,0 +1,97 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use chrono::{DateTime, Local};
+use crate::backend::persistence::{Article, ArticleId};
+
+// Maps each tag to the live articles that have it, so that tags can be listed without reading every article.
+// Articles in the trash are not indexed.
+#[derive(Default)]
+pub struct TagIndex {
+    articles: BTreeMap<String, BTreeMap<ArticleId, Visibility>>,
+    // the tags each article is indexed under, to find its entries again when it changes
+    tags: HashMap<ArticleId, BTreeSet<String>>,
+}
+
+// enough of the article to tell whether it is visible at a given moment, as scheduled articles become visible on their own
+#[derive(Copy, Clone)]
+struct Visibility {
+    visible: bool,
+    publish_at: Option<DateTime<Local>>,
+}
+
+impl Visibility {
+    const fn of(article: &Article) -> Self {
+        Self {
+            visible: article.visible,
+            publish_at: article.publish_at,
+        }
+    }
+
+    // anything is visible to the author, who passes None
+    fn is_visible_at(self, now: Option<DateTime<Local>>) -> bool {
+        now.is_none_or(|now| self.visible || self.publish_at.is_some_and(|publish_at| publish_at <= now))
+    }
+}
+
+impl TagIndex {
+    pub fn build<'a>(articles: impl IntoIterator<Item = &'a Article>) -> Self {
+        let mut index = Self::default();
+        for article in articles {
+            index.update(article);
+        }
+
+        index
+    }
+
+    // must be called with every article that is stored
+    pub fn update(&mut self, article: &Article) {
+        self.remove(&article.id);
+        if article.deleted_at.is_some() || article.metadata.tags.is_empty() {
+            return
+        }
+
+        for tag in &article.metadata.tags {
+            self.articles.entry(tag.clone()).or_default().insert(article.id.clone(), Visibility::of(article));
+        }
+        self.tags.insert(article.id.clone(), article.metadata.tags.clone());
+    }
+
+    pub fn remove(&mut self, article_id: &ArticleId) {
+        let Some(tags) = self.tags.remove(article_id) else {
+            return
+        };
+
+        for tag in tags {
+            if let Some(articles) = self.articles.get_mut(&tag) {
+                articles.remove(article_id);
+                if articles.is_empty() {
+                    self.articles.remove(&tag);
+                }
+            }
+        }
+    }
+
+    // Tags in ascending order, with how many articles have each. Drafts are left out if `visible_at` is set,
+    // unless they are scheduled to be visible by then.
+    pub fn counts(&self, visible_at: Option<DateTime<Local>>) -> Vec<(String, usize)> {
+        self.articles.iter()
+            .map(|(tag, articles)| {
+                let count = articles.values().filter(|visibility| visibility.is_visible_at(visible_at)).count();
+                (tag.clone(), count)
+            })
+            .filter(|(_, count)| *count > 0)
+            .collect()
+    }
+
+    pub fn contains(&self, tag: &str, visible_at: Option<DateTime<Local>>) -> bool {
+        self.articles.get(tag).is_some_and(|articles| articles.values().any(|visibility| visibility.is_visible_at(visible_at)))
+    }
+
+    pub fn articles(&self, tag: &str, visible_at: Option<DateTime<Local>>) -> Vec<ArticleId> {
+        self.articles.get(tag).map_or_else(Vec::new, |articles| {
+            articles.iter()
+                .filter(|(_, visibility)| visibility.is_visible_at(visible_at))
+                .map(|(article_id, _)| article_id.clone())
+                .collect()
+        })
+    }
+}
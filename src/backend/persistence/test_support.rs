@@ -0,0 +1,29 @@
+use std::fs;
+use std::path::PathBuf;
+use chrono::{DateTime, Local};
+use crate::backend::persistence::blob::BlobStore;
+use crate::backend::persistence::json_file::{FlushPolicy, JsonFileStorage};
+use crate::backend::persistence::{Article, ArticleId, ArticleRepository, Metadata};
+
+// A fresh directory for each test. Storages refuse to open a path that is already open in this process,
+// so tests must not share one.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("toy-blog-test-{pid}-{name}", pid = std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+pub fn temp_repository(name: &str) -> ArticleRepository {
+    let directory = temp_dir(name);
+    let storage = JsonFileStorage::open(directory.join("index.json"), FlushPolicy::OnWrite).unwrap();
+    ArticleRepository::new(Box::new(storage), BlobStore::open(directory.join("attachments")).unwrap()).unwrap()
+}
+
+pub fn published_article(id: &str, created_at: DateTime<Local>) -> Article {
+    Article {
+        created_at,
+        updated_at: created_at,
+        ..Article::new(ArticleId::new(id.to_string()), String::new(), Metadata::default()).published()
+    }
+}
@@ -0,0 +1,156 @@
+use std::fs;
+use std::num::NonZeroU64;
+use std::path::PathBuf;
+use actix_web::http::header::HeaderName;
+use actix_web::http::Method;
+use anyhow::{bail, Context as _, Result};
+use clap::Parser;
+use log::LevelFilter;
+
+#[derive(Parser, Clone, Debug)]
+#[clap(author, version, about)]
+pub struct Config {
+    /// Address to listen on.
+    #[clap(long, env = "TOY_BLOG_BIND", default_value = "127.0.0.1")]
+    pub bind: String,
+
+    /// Port to listen on.
+    #[clap(short, long, env = "TOY_BLOG_PORT", default_value_t = 8080)]
+    pub port: u16,
+
+    /// How articles are persisted.
+    #[clap(long, env = "TOY_BLOG_STORAGE", value_enum, default_value_t = StorageKind::Json)]
+    pub storage: StorageKind,
+
+    /// File or directory where articles are persisted.
+    /// Defaults to index.json, index.sqlite3 or articles depending on the storage.
+    #[clap(long, env = "TOY_BLOG_DATA_PATH")]
+    pub data_path: Option<PathBuf>,
+
+    /// Imports articles from a JSON file written by the json storage at startup.
+    /// Articles that already exist in the storage are skipped.
+    #[clap(long, env = "TOY_BLOG_IMPORT_FROM")]
+    pub import_from: Option<PathBuf>,
+
+    /// File where logs are written in addition to stdout.
+    #[clap(long, env = "TOY_BLOG_LOG_FILE", default_value = "output.log")]
+    pub log_file: PathBuf,
+
+    /// One of off, error, warn, info, debug and trace.
+    #[clap(long, env = "TOY_BLOG_LOG_LEVEL", default_value = "debug", value_parser = clap::value_parser!(LevelFilter))]
+    pub log_level: LevelFilter,
+
+    /// Write mutations to the data file at most once per this many milliseconds instead of on every request.
+    /// Mutations made within the last interval are lost if the process crashes.
+    /// Only the json storage buffers writes, so this cannot be used with the others.
+    #[clap(long, env = "TOY_BLOG_FLUSH_INTERVAL_MS")]
+    pub flush_interval_ms: Option<NonZeroU64>,
+
+    /// Days an article stays in the trash after being deleted before it is erased permanently.
+    #[clap(long, env = "TOY_BLOG_TRASH_RETENTION_DAYS", default_value_t = 30)]
+    pub trash_retention_days: u32,
+
+    /// Number of worker threads. Defaults to the number of physical CPU cores.
+    #[clap(long, env = "TOY_BLOG_WORKERS")]
+    pub workers: Option<usize>,
+
+    #[clap(flatten)]
+    pub auth: AuthConfig,
+
+    #[clap(flatten)]
+    pub cors: CorsConfig,
+
+    #[clap(flatten)]
+    pub attachments: AttachmentConfig,
+}
+
+#[derive(clap::ValueEnum, Eq, PartialEq, Copy, Clone, Debug)]
+pub enum StorageKind {
+    /// All articles in a single JSON file
+    Json,
+    /// Embedded SQL database
+    Sqlite,
+    /// One markdown file per article in a directory
+    Directory,
+}
+
+impl Config {
+    pub fn data_path(&self) -> PathBuf {
+        self.data_path.clone().unwrap_or_else(|| match self.storage {
+            StorageKind::Json => PathBuf::from("index.json"),
+            StorageKind::Sqlite => PathBuf::from("index.sqlite3"),
+            StorageKind::Directory => PathBuf::from("articles"),
+        })
+    }
+}
+
+#[derive(clap::Args, Clone, Debug)]
+pub struct AuthConfig {
+    /// Bearer token required by the endpoints that modify articles.
+    #[clap(long, env = "TOY_BLOG_BEARER_TOKEN", hide_env_values = true, conflicts_with = "bearer-token-file")]
+    pub bearer_token: Option<String>,
+
+    /// File whose content is used as the bearer token. Surrounding whitespace is ignored.
+    #[clap(long, env = "TOY_BLOG_BEARER_TOKEN_FILE")]
+    pub bearer_token_file: Option<PathBuf>,
+}
+
+impl AuthConfig {
+    pub fn load_bearer_token(&self) -> Result<String> {
+        let token = match (&self.bearer_token, &self.bearer_token_file) {
+            (Some(token), _) => token.clone(),
+            (None, Some(path)) => fs::read_to_string(path)
+                .with_context(|| format!("reading bearer token from {path}", path = path.display()))?,
+            (None, None) => bail!("no bearer token is configured; use --bearer-token or --bearer-token-file"),
+        };
+
+        let token = token.trim();
+        if token.is_empty() {
+            bail!("the configured bearer token is empty");
+        }
+
+        Ok(token.to_string())
+    }
+}
+
+#[derive(clap::Args, Clone, Debug)]
+pub struct CorsConfig {
+    /// Origins allowed to call the API from a browser. `*` allows any origin.
+    #[clap(long = "cors-allowed-origin", env = "TOY_BLOG_CORS_ALLOWED_ORIGINS", value_delimiter = ',')]
+    pub allowed_origins: Vec<String>,
+
+    /// Methods allowed in cross-origin requests.
+    #[clap(
+        long = "cors-allowed-method",
+        env = "TOY_BLOG_CORS_ALLOWED_METHODS",
+        value_delimiter = ',',
+        default_value = "GET,POST,PUT,DELETE",
+        value_parser = clap::value_parser!(Method),
+    )]
+    pub allowed_methods: Vec<Method>,
+
+    /// Request headers allowed in cross-origin requests. `Authorization` is always allowed.
+    #[clap(
+        long = "cors-allowed-header",
+        env = "TOY_BLOG_CORS_ALLOWED_HEADERS",
+        value_delimiter = ',',
+        default_value = "Content-Type,If-Match,If-None-Match,If-Modified-Since,If-Unmodified-Since",
+        value_parser = clap::value_parser!(HeaderName),
+    )]
+    pub allowed_headers: Vec<HeaderName>,
+
+    /// How long browsers may cache the preflight response, in seconds.
+    #[clap(long = "cors-max-age", env = "TOY_BLOG_CORS_MAX_AGE", default_value_t = 3600)]
+    pub max_age: usize,
+}
+
+#[derive(clap::Args, Clone, Debug)]
+pub struct AttachmentConfig {
+    /// Directory where the bodies of attachments are stored, whichever storage is used for articles.
+    #[clap(long = "attachments-path", env = "TOY_BLOG_ATTACHMENTS_PATH", default_value = "attachments")]
+    pub path: PathBuf,
+
+    /// Largest attachment that can be uploaded, in bytes.
+    #[clap(long = "attachment-size-limit", env = "TOY_BLOG_ATTACHMENT_SIZE_LIMIT", default_value_t = 10 * 1024 * 1024)]
+    pub size_limit: usize,
+}
@@ -2,18 +2,34 @@
 #![warn(clippy::pedantic, clippy::nursery)]
 
 mod backend;
+mod config;
 mod extension;
 
 // TODO: telnetサポートしたら面白いんじゃね？ - @yanorei32
 
+use std::path::Path;
+use std::time::Duration;
+use actix_cors::Cors;
 use actix_web::{App, HttpServer};
 
-use actix_web::web::{scope as prefixed_service};
-use anyhow::{Result, Context as _};
+use actix_web::http::header::{AUTHORIZATION, ETAG, LAST_MODIFIED};
+use actix_web::web::{Data, scope as prefixed_service};
+use anyhow::{bail, Result, Context as _};
+use clap::Parser;
 use fern::colors::ColoredLevelConfig;
-use crate::backend::api::article;
+use log::{error, info, LevelFilter};
+use crate::backend::api::{article, attachment, listing, revision, tag, trash};
+use crate::backend::api::auth::BearerToken;
+use crate::backend::persistence::{ArticleRepository, ArticleStorage};
+use crate::backend::persistence::blob::BlobStore;
+use crate::backend::persistence::directory::DirectoryStorage;
+use crate::backend::persistence::json_file::{FlushPolicy, JsonFileStorage};
+use crate::backend::persistence::sqlite::SqliteStorage;
+use crate::config::{Config, CorsConfig, StorageKind};
 
-fn setup_logger() -> Result<()> {
+const TRASH_PURGE_INTERVAL: Duration = Duration::from_hours(1);
+
+fn setup_logger(level: LevelFilter, log_file: &Path) -> Result<()> {
     let colors = ColoredLevelConfig::new();
     fern::Dispatch::new()
         .format(move |out, message, record| {
@@ -25,43 +41,171 @@ fn setup_logger() -> Result<()> {
                 message
             ));
         })
-        .level(log::LevelFilter::Debug)
+        .level(level)
         .chain(std::io::stdout())
-        .chain(fern::log_file("output.log")?)
+        .chain(fern::log_file(log_file)?)
         .apply()?;
     Ok(())
 }
 
+fn build_cors(config: &CorsConfig) -> Cors {
+    let cors = Cors::default()
+        .allowed_methods(config.allowed_methods.clone())
+        .allowed_header(AUTHORIZATION)
+        .allowed_headers(config.allowed_headers.clone())
+        // so that editors can send them back in conditional requests
+        .expose_headers([ETAG, LAST_MODIFIED])
+        .max_age(config.max_age);
+
+    config.allowed_origins.iter().fold(cors, |cors, origin| {
+        if origin == "*" {
+            cors.allow_any_origin()
+        } else {
+            cors.allowed_origin(origin)
+        }
+    })
+}
+
 #[actix_web::main]
 async fn main() -> Result<()> {
-    setup_logger().unwrap_or_default();
-
-    let server = HttpServer::new(|| {
-        App::new()
-            .service(prefixed_service("/api")
-                .service(
-                    (
-                        prefixed_service("/article")
-                            .service(
-                                (
-                                    article::create,
-                                    article::fetch,
-                                    article::update,
-                                    article::remove,
+    let config = Config::parse();
+    setup_logger(config.log_level, &config.log_file).unwrap_or_default();
+
+    let bearer_token = config.auth.load_bearer_token().context("while loading credentials")?;
+    let bearer_token = Data::new(BearerToken::new(&bearer_token));
+
+    // the other storages write every mutation through, so the flush task would do nothing for them
+    if config.flush_interval_ms.is_some() && config.storage != StorageKind::Json {
+        bail!("--flush-interval-ms is supported only by the json storage");
+    }
+
+    let flush_policy = if config.flush_interval_ms.is_some() {
+        FlushPolicy::Deferred
+    } else {
+        FlushPolicy::OnWrite
+    };
+    let data_path = config.data_path();
+    let storage: Box<dyn ArticleStorage> = match config.storage {
+        StorageKind::Json => Box::new(JsonFileStorage::open(&data_path, flush_policy).context("while opening data file")?),
+        StorageKind::Sqlite => Box::new(SqliteStorage::open(&data_path).context("while opening database")?),
+        StorageKind::Directory => Box::new(DirectoryStorage::open(&data_path).context("while opening directory")?),
+    };
+    let blobs = BlobStore::open(&config.attachments.path).context("while opening attachments directory")?;
+    let repository = Data::new(ArticleRepository::new(storage, blobs).context("while indexing articles")?);
+    let attachment_config = Data::new(config.attachments.clone());
+
+    if let Some(path) = &config.import_from {
+        let articles = JsonFileStorage::read_articles(path).context("while reading articles to import")?;
+        let imported = repository.import(articles).context("while importing articles")?;
+        info!("imported {imported} articles from {path}", path = path.display());
+    }
+
+    if let Some(interval) = config.flush_interval_ms {
+        let repository = repository.clone();
+        actix_web::rt::spawn(async move {
+            let mut interval = actix_web::rt::time::interval(Duration::from_millis(interval.get()));
+            loop {
+                interval.tick().await;
+                if let Err(err) = repository.flush() {
+                    error!("failed to flush: {err:?}");
+                }
+            }
+        });
+    }
+
+    {
+        let repository = repository.clone();
+        let retention = chrono::Duration::days(config.trash_retention_days.into());
+        actix_web::rt::spawn(async move {
+            let mut interval = actix_web::rt::time::interval(TRASH_PURGE_INTERVAL);
+            loop {
+                interval.tick().await;
+                match repository.purge(retention) {
+                    Ok(0) => {}
+                    Ok(purged) => info!("purged {purged} articles from the trash"),
+                    Err(err) => error!("failed to purge the trash: {err:?}"),
+                }
+            }
+        });
+    }
+
+    let bind = (config.bind.clone(), config.port);
+    let workers = config.workers;
+
+    let mut server = HttpServer::new({
+        let repository = repository.clone();
+        move || {
+            App::new()
+                .app_data(bearer_token.clone())
+                .app_data(repository.clone())
+                .app_data(attachment_config.clone())
+                .service(prefixed_service("/api")
+                    .wrap(build_cors(&config.cors))
+                    .service(
+                        (
+                            prefixed_service("/article")
+                                .service(
+                                    (
+                                        article::create,
+                                        article::fetch_html,
+                                        article::fetch,
+                                        article::update,
+                                        article::remove,
+                                        article::publish,
+                                        article::unpublish,
+                                    )
+                                )
+                                .service(
+                                    (
+                                        revision::list,
+                                        revision::fetch,
+                                        revision::diff,
+                                        revision::restore,
+                                    )
                                 )
-                            ),
-                        article::list,
+                                .service(
+                                    (
+                                        attachment::list,
+                                        attachment::upload,
+                                        attachment::download,
+                                        attachment::remove,
+                                    )
+                                ),
+                            prefixed_service("/trash")
+                                .service(
+                                    (
+                                        trash::list,
+                                        trash::restore,
+                                    )
+                                ),
+                            prefixed_service("/tags")
+                                .app_data(listing::query_config())
+                                .service(
+                                    (
+                                        tag::list,
+                                        tag::articles,
+                                    )
+                                ),
+                            prefixed_service("/articles")
+                                .app_data(listing::query_config())
+                                .service(article::list),
+                        )
                     )
                 )
-            )
+        }
     });
 
+    if let Some(workers) = workers {
+        server = server.workers(workers);
+    }
 
     server
-        .bind(("127.0.0.1", 8080))?
+        .bind(bind)?
         .run()
         .await
         .context("while running server")?;
 
+    repository.flush().context("while flushing pending changes")?;
+
     Ok(())
 }